@@ -1,5 +1,13 @@
+#[cfg(feature = "tokio")]
+pub mod async_tachyon;
 pub mod channel;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod connection;
+pub mod delivery;
+pub mod discovery;
+pub mod error;
+pub mod event;
 pub mod ffi;
 pub mod fragmentation;
 pub mod header;
@@ -10,14 +18,22 @@ pub mod pool;
 pub mod pool_ffi;
 pub mod receive_result;
 pub mod receiver;
+pub mod rpc;
 pub mod send_buffer_manager;
 pub mod sequence;
+pub mod scheduler;
 pub mod sequence_buffer;
+pub mod stream;
 pub mod tachyon_socket;
+pub mod topic;
 pub mod unreliable_sender;
+pub mod unreliable_sequence;
 pub mod byte_buffer_pool;
 pub mod pool_unreliable_sender;
 pub mod memory_block;
+pub mod mtu;
+#[cfg(feature = "serde")]
+pub mod typed;
 
 mod connection_impl;
 
@@ -25,28 +41,44 @@ mod connection_impl;
 #[cfg(test)]
 pub mod tachyon_test;
 
+use std::collections::VecDeque;
 use std::time::Duration;
 use std::time::Instant;
 
 use rustc_hash::FxHashMap;
+use rustc_hash::FxHashSet;
 
 use self::channel::*;
 use self::connection::*;
 use self::connection_impl::ConnectionEventCallback;
+use self::connection_impl::ConnectionEventHandler;
+use self::connection_impl::IdentityEventHandler;
 use self::connection_impl::IDENTITY_LINKED_EVENT;
 use self::connection_impl::IDENTITY_UNLINKED_EVENT;
 use self::connection_impl::LINK_IDENTITY_EVENT;
 use self::connection_impl::IdentityEventCallback;
 use self::connection_impl::UNLINK_IDENTITY_EVENT;
+use self::delivery::DeliveryEvent;
+use self::delivery::DeliveryEventCallback;
+use self::error::TachyonError;
+use self::event::Event;
+use self::event::MAX_QUEUED_EVENTS;
 use self::fragmentation::*;
 use self::header::*;
+use self::mtu::MtuDiscovery;
 use self::network_address::NetworkAddress;
 use self::pool::SendTarget;
 use self::receive_result::ReceiveResult;
 use self::receive_result::TachyonReceiveResult;
+use self::receive_result::RECEIVE_ERROR_BUFFER_TOO_SMALL;
 use self::receive_result::RECEIVE_ERROR_CHANNEL;
 use self::receive_result::RECEIVE_ERROR_UNKNOWN;
+use self::scheduler::TokenBucket;
+use self::stream::StreamQueue;
+use self::byte_buffer_pool::BYTE_BUFFER_SIZE_DEFAULT;
 use self::tachyon_socket::*;
+use self::topic::PublishResult;
+use self::topic::Topics;
 use self::unreliable_sender::UnreliableSender;
 
 pub const SEND_ERROR_CHANNEL: u32 = 2;
@@ -55,10 +87,20 @@ pub const SEND_ERROR_FRAGMENT: u32 = 3;
 pub const SEND_ERROR_UNKNOWN: u32 = 4;
 pub const SEND_ERROR_LENGTH: u32 = 5;
 pub const SEND_ERROR_IDENTITY: u32 = 6;
+pub const SEND_ERROR_MESSAGE_SIZE: u32 = 7;
+pub const SEND_ERROR_UNKNOWN_TARGET: u32 = 8;
 
 
 const SOCKET_RECEIVE_BUFFER_LEN: usize = 1024 * 1024;
 
+pub const MAX_DATAGRAM_SIZE_DEFAULT: usize = BYTE_BUFFER_SIZE_DEFAULT;
+pub const MAX_DATAGRAM_SIZE_MIN: usize = 256;
+// largest udp payload over ipv4
+pub const MAX_DATAGRAM_SIZE_MAX: usize = 65507;
+
+pub const MAX_FRAGMENT_BYTES_DEFAULT: usize = 4 * 1024 * 1024;
+pub const MAX_MESSAGE_SIZE_DEFAULT: usize = 1024 * 1024;
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default, Debug)]
@@ -87,7 +129,43 @@ impl std::fmt::Display for TachyonStats {
 pub struct TachyonConfig {
     pub use_identity: u32,
     pub drop_packet_chance: u64,
-    pub drop_reliable_only: u32
+    pub drop_reliable_only: u32,
+    // 0 uses MAX_DATAGRAM_SIZE_DEFAULT. Peers use the smaller of the two once settings are exchanged.
+    pub max_datagram_size: u32,
+    // probe each connection for the largest datagram that gets through and fragment to that
+    pub use_mtu_discovery: u32,
+    // cap on bytes held in partially received fragment groups per connection, oldest groups are dropped past it.
+    // 0 uses MAX_FRAGMENT_BYTES_DEFAULT.
+    pub max_fragment_bytes: u32,
+    // largest reliable message, 0 uses MAX_MESSAGE_SIZE_DEFAULT. Non stream channels are also limited to
+    // fragment counts that fit in half the receive window.
+    pub max_message_size: u32,
+    // bytes per second of reliable traffic per connection, 0 is unlimited. When set reliable channels queue like
+    // stream channels and the scheduler sends by channel priority and weight.
+    pub send_rate_limit: u32
+}
+
+impl TachyonConfig {
+    pub fn get_max_datagram_size(&self) -> usize {
+        if self.max_datagram_size == 0 {
+            return MAX_DATAGRAM_SIZE_DEFAULT;
+        }
+        return (self.max_datagram_size as usize).clamp(MAX_DATAGRAM_SIZE_MIN, MAX_DATAGRAM_SIZE_MAX);
+    }
+
+    pub fn get_max_message_size(&self) -> usize {
+        if self.max_message_size == 0 {
+            return MAX_MESSAGE_SIZE_DEFAULT;
+        }
+        return self.max_message_size as usize;
+    }
+
+    pub fn get_max_fragment_bytes(&self) -> usize {
+        if self.max_fragment_bytes == 0 {
+            return MAX_FRAGMENT_BYTES_DEFAULT;
+        }
+        return self.max_fragment_bytes as usize;
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -114,9 +192,31 @@ pub struct Tachyon {
     pub stats: TachyonStats,
     pub start_time: Instant,
     pub last_identity_link_request: Instant,
+    pub last_settings_request: Instant,
+    pub settings_confirmed: bool,
+    pub mtu_discovery: FxHashMap<NetworkAddress, MtuDiscovery>,
+    // unreliable message too large for the last receive buffer, still in socket_receive_buffer
+    pub pending_unreliable: Option<(usize, NetworkAddress, u8)>,
     pub identity: Identity,
     pub identity_event_callback: Option<IdentityEventCallback>,
-    pub connection_event_callback: Option<ConnectionEventCallback>
+    pub connection_event_callback: Option<ConnectionEventCallback>,
+    pub delivery_event_callback: Option<DeliveryEventCallback>,
+    pub identity_event_handler: Option<IdentityEventHandler>,
+    pub connection_event_handler: Option<ConnectionEventHandler>,
+    pub delivery_events: VecDeque<DeliveryEvent>,
+    pub send_buckets: FxHashMap<NetworkAddress, TokenBucket>,
+    // configured channel ids by priority, highest first
+    pub channel_order: Vec<u8>,
+    // created because the other side of some connection has them configured
+    pub negotiated_channel_ids: Vec<u8>,
+    pub channel_mismatches: VecDeque<ChannelMismatch>,
+    // zstd dictionaries by channel id
+    pub compression_dictionaries: FxHashMap<u8, Vec<u8>>,
+    pub events: VecDeque<Event>,
+    // messages poll_events has added to events, they don't count against MAX_QUEUED_EVENTS
+    event_messages: usize,
+    pub topics: Topics,
+    event_receive_buffer: Vec<u8>
 }
 
 impl Tachyon {
@@ -142,13 +242,31 @@ impl Tachyon {
             stats: TachyonStats::default(),
             start_time: Instant::now(),
             last_identity_link_request: Instant::now() - Duration::new(100, 0),
+            last_settings_request: Instant::now() - Duration::new(100, 0),
+            settings_confirmed: false,
+            mtu_discovery: FxHashMap::default(),
+            pending_unreliable: None,
             identity: Identity::default(),
             identity_event_callback: None,
-            connection_event_callback: None
+            connection_event_callback: None,
+            delivery_event_callback: None,
+            identity_event_handler: None,
+            connection_event_handler: None,
+            delivery_events: VecDeque::new(),
+            send_buckets: FxHashMap::default(),
+            channel_order: Vec::new(),
+            negotiated_channel_ids: Vec::new(),
+            channel_mismatches: VecDeque::new(),
+            compression_dictionaries: FxHashMap::default(),
+            events: VecDeque::new(),
+            event_messages: 0,
+            topics: Topics::create(),
+            event_receive_buffer: vec![0; 4096]
         };
 
         tachyon.channel_config.insert(1, ChannelConfig::default_ordered());
         tachyon.channel_config.insert(2, ChannelConfig::default_unordered());
+        tachyon.update_channel_order();
 
         return tachyon;
     }
@@ -159,29 +277,26 @@ impl Tachyon {
     }
 
     pub fn bind(&mut self, address: NetworkAddress) -> bool {
-        match self.socket.bind_socket(address) {
-            CreateConnectResult::Success => {
-                self.unreliable_sender = self.create_unreliable_sender();
-                return true;
-            }
-            CreateConnectResult::Error => {
-                return false;
-            }
-        }
+        return self.try_bind(address).is_ok();
+    }
+
+    pub fn try_bind(&mut self, address: NetworkAddress) -> Result<(), TachyonError> {
+        self.socket.try_bind_socket(address)?;
+        self.unreliable_sender = self.create_unreliable_sender();
+        return Ok(());
     }
 
     pub fn connect(&mut self, address: NetworkAddress) -> bool {
-        match self.socket.connect_socket(address) {
-            CreateConnectResult::Success => {
-                let local_address = NetworkAddress::default();
-                self.create_connection(local_address, Identity::default());
-                self.unreliable_sender = self.create_unreliable_sender();
-                return true;
-            }
-            CreateConnectResult::Error => {
-                return false;
-            }
-        }
+        return self.try_connect(address).is_ok();
+    }
+
+    pub fn try_connect(&mut self, address: NetworkAddress) -> Result<(), TachyonError> {
+        self.socket.try_connect_socket(address)?;
+        let local_address = NetworkAddress::default();
+        self.settings_confirmed = false;
+        self.create_connection(local_address, Identity::default());
+        self.unreliable_sender = self.create_unreliable_sender();
+        return Ok(());
     }
 
     pub fn create_unreliable_sender(&self) -> Option<UnreliableSender> {
@@ -205,15 +320,88 @@ impl Tachyon {
     }
 
     fn create_configured_channels(&mut self, address: NetworkAddress) {
+        let max_datagram_size = self.config.get_max_datagram_size();
         for (channel_id,config) in &self.channel_config {
             match self.channels.get_mut(&(address, *channel_id)) {
                 Some(_) => {}
                 None => {
-                    let channel = Channel::create(*channel_id, address, *config);
+                    let dictionary = self.compression_dictionaries.get(channel_id);
+                    let channel = Tachyon::create_channel(self.config, *channel_id, address, *config, max_datagram_size, dictionary);
                     self.channels.insert((address, *channel_id), channel);
                 }
             }
         }
+
+        self.apply_max_datagram_size(address);
+    }
+
+    // buffers stay sized to our own max, only the fragment size follows the connection and it's mtu
+    fn apply_max_datagram_size(&mut self, address: NetworkAddress) {
+        let conn = match self.connections.get(&address) {
+            Some(conn) => conn,
+            None => {
+                return;
+            }
+        };
+
+        let mut datagram_size = conn.max_datagram_size as usize;
+        if conn.mtu > 0 {
+            datagram_size = std::cmp::min(datagram_size, conn.mtu as usize);
+        }
+
+        for channel_id in self.channel_config.keys() {
+            if let Some(channel) = self.channels.get_mut(&(address, *channel_id)) {
+                channel.frag.fragment_size = Fragmentation::fragment_size_for(datagram_size);
+            }
+        }
+    }
+
+    pub fn get_fragment_bytes(&self, address: NetworkAddress) -> usize {
+        let mut bytes = 0;
+        for channel_id in self.channel_config.keys() {
+            if let Some(channel) = self.channels.get(&(address, *channel_id)) {
+                bytes += channel.frag.received_bytes;
+            }
+        }
+        return bytes;
+    }
+
+    // evicts the oldest group across the connection's channels until under the limit
+    fn enforce_fragment_limit(&mut self, address: NetworkAddress) {
+        let max_bytes = self.config.get_max_fragment_bytes();
+        let mut bytes = self.get_fragment_bytes(address);
+
+        while bytes > max_bytes {
+            let mut oldest: Option<(u8, Instant)> = None;
+            for channel_id in self.channel_config.keys() {
+                if let Some(channel) = self.channels.get(&(address, *channel_id)) {
+                    if let Some(received_at) = channel.frag.oldest_received_at() {
+                        if oldest.is_none() || received_at < oldest.unwrap().1 {
+                            oldest = Some((*channel_id, received_at));
+                        }
+                    }
+                }
+            }
+
+            let channel_id = match oldest {
+                Some(v) => v.0,
+                None => {
+                    return;
+                }
+            };
+
+            let mut lost = 0;
+            if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+                let before = channel.frag.received_bytes;
+                if !channel.frag.evict_oldest() {
+                    return;
+                }
+                bytes -= before - channel.frag.received_bytes;
+                channel.report_dropped_groups();
+                lost = channel.frag.take_lost();
+            }
+            self.push_lost_messages(address, channel_id, lost);
+        }
     }
 
     pub fn get_channel_count(&mut self, address: NetworkAddress) -> u32 {
@@ -227,21 +415,146 @@ impl Tachyon {
         return count;
     }
 
-    fn remove_configured_channels(&mut self, address: NetworkAddress) {
-        for config in &self.channel_config {
-            let channel_id = *config.0;
-            self.channels.remove(&(address, channel_id));
+    fn create_channel(tachyon_config: TachyonConfig, channel_id: u8, address: NetworkAddress, config: ChannelConfig, max_datagram_size: usize,
+        dictionary: Option<&Vec<u8>>) -> Channel {
+        let mut channel = Channel::create(channel_id, address, config, max_datagram_size);
+        #[cfg(feature = "compression")]
+        {
+            channel.compression.max_message_size = tachyon_config.get_max_message_size();
+            channel.compression.set_dictionary(dictionary.cloned());
+        }
+        #[cfg(not(feature = "compression"))]
+        let _ = dictionary;
+        // a group larger then the byte cap would be evicted before it completes
+        channel.frag.max_message_size = tachyon_config.get_max_message_size().min(tachyon_config.get_max_fragment_bytes());
+        // rate limited traffic waits in the queue for the scheduler
+        if tachyon_config.send_rate_limit > 0 && channel.mode == CHANNEL_MODE_RELIABLE && !channel.is_stream() {
+            channel.stream = Some(StreamQueue::default());
         }
+        return channel;
     }
 
+    // create a channel only the other side has configured, for just that connection
+    pub(crate) fn create_negotiated_channel(&mut self, address: NetworkAddress, channel_id: u8, config: ChannelConfig) {
+        let dictionary = self.compression_dictionaries.get(&channel_id);
+        let channel = Tachyon::create_channel(self.config, channel_id, address, config, self.config.get_max_datagram_size(), dictionary);
+        self.channels.insert((address, channel_id), channel);
+        self.apply_max_datagram_size(address);
+
+        if !self.negotiated_channel_ids.contains(&channel_id) {
+            self.negotiated_channel_ids.push(channel_id);
+            self.update_channel_order();
+        }
+    }
+
+    // includes channels created from negotiation
+    fn remove_configured_channels(&mut self, address: NetworkAddress) {
+        self.channels.retain(|key, _| key.0 != address);
+    }
+
+    // Channels can be added while connected, they are created on every connection and announced to the other side.
+    // A channel that's in use can't be reconfigured, remove it first.
     pub fn configure_channel(&mut self, channel_id: u8, config: ChannelConfig) -> bool {
-        if channel_id < 3 {
+        if channel_id == 0 {
             return false;
         }
+
+        if self.channel_config.contains_key(&channel_id) && self.connections.len() > 0 {
+            return false;
+        }
+
         self.channel_config.insert(channel_id, config);
+        self.update_channel_order();
+
+        if self.connections.len() > 0 {
+            let addresses: Vec<NetworkAddress> = self.connections.keys().copied().collect();
+            for address in addresses {
+                // replaces one created from negotiation
+                self.channels.remove(&(address, channel_id));
+                self.create_configured_channels(address);
+            }
+            self.announce_channels();
+        }
+        return true;
+    }
+
+    // Removes the channel from every connection, anything queued or unacked on it is dropped.
+    pub fn remove_channel(&mut self, channel_id: u8) -> bool {
+        if self.channel_config.remove(&channel_id).is_none() {
+            return false;
+        }
+
+        self.channels.retain(|key, _| key.1 != channel_id);
+        self.negotiated_channel_ids.retain(|id| *id != channel_id);
+        self.update_channel_order();
+        self.announce_channels();
         return true;
     }
 
+    fn update_channel_order(&mut self) {
+        self.channel_order = self.channel_config.keys().copied().collect();
+        for channel_id in &self.negotiated_channel_ids {
+            if !self.channel_order.contains(channel_id) {
+                self.channel_order.push(*channel_id);
+            }
+        }
+        let configs = &self.channel_config;
+        self.channel_order.sort_by_key(|id| {
+            let priority = match configs.get(id) {
+                Some(config) => config.priority,
+                None => 0
+            };
+            return (std::cmp::Reverse(priority), *id);
+        });
+    }
+
+    // Pre-trained zstd dictionary for a channel, both sides have to use the same one.  Set it before connecting,
+    // messages in flight when it changes fail to decompress.  An empty dictionary clears it.
+    pub fn set_compression_dictionary(&mut self, channel_id: u8, dictionary: &[u8]) {
+        let dictionary = if dictionary.is_empty() { None } else { Some(dictionary.to_vec()) };
+        match &dictionary {
+            Some(dictionary) => {
+                self.compression_dictionaries.insert(channel_id, dictionary.clone());
+            }
+            None => {
+                self.compression_dictionaries.remove(&channel_id);
+            }
+        }
+        #[cfg(feature = "compression")]
+        for (key, channel) in self.channels.iter_mut() {
+            if key.1 == channel_id {
+                channel.compression.set_dictionary(dictionary.clone());
+            }
+        }
+    }
+
+    pub fn take_channel_mismatch(&mut self) -> Option<ChannelMismatch> {
+        return self.channel_mismatches.pop_front();
+    }
+
+    // true once the other side has acked the sequence sent on this channel
+    pub fn is_delivered(&mut self, address: NetworkAddress, channel_id: u8, sequence: u16) -> bool {
+        match self.channels.get(&(address, channel_id)) {
+            Some(channel) => {
+                return channel.is_delivered(sequence);
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    pub fn get_rtt_micros(&mut self, address: NetworkAddress, channel_id: u8) -> u64 {
+        match self.channels.get(&(address, channel_id)) {
+            Some(channel) => {
+                return channel.rtt_micros;
+            }
+            None => {
+                return 0;
+            }
+        }
+    }
+
     pub fn get_combined_stats(&mut self) -> TachyonStats {
         let mut channel_stats = ChannelStats::default();
         for channel in self.channels.values_mut() {
@@ -255,20 +568,164 @@ impl Tachyon {
 
     pub fn update(&mut self) {
         self.client_identity_update();
+        self.client_settings_update();
+        self.mtu_update();
 
+        let mut lost: Vec<(NetworkAddress, u8, u64)> = Vec::new();
         for channel in self.channels.values_mut() {
             channel.update(&self.socket);
+            let count = channel.frag.take_lost();
+            if count > 0 {
+                lost.push((channel.address, channel.id, count));
+            }
+
+            // with a callback registered events go only to the callback, otherwise they queue up for take_delivery_event
+            while let Some(event) = channel.delivery.events.pop_front() {
+                if let Some(callback) = self.delivery_event_callback {
+                    unsafe {
+                        callback(event);
+                    }
+                } else {
+                    // capped like events, for apps that send with a ttl and never take them
+                    if self.delivery_events.len() >= MAX_QUEUED_EVENTS {
+                        self.delivery_events.pop_front();
+                    }
+                    self.delivery_events.push_back(event);
+                }
+            }
+        }
+
+        for (address, channel_id, count) in lost {
+            self.push_lost_messages(address, channel_id, count);
+        }
+
+        let mut queued: Vec<NetworkAddress> = Vec::new();
+        for channel in self.channels.values() {
+            if channel.has_queued() && !queued.contains(&channel.address) {
+                queued.push(channel.address);
+            }
+        }
+        for address in queued {
+            self.schedule_connection(address);
+        }
+    }
+
+    // Send queued messages on the connection's channels by priority and weight, within the send rate limit if set.
+    // Returns bytes sent.
+    fn schedule_connection(&mut self, address: NetworkAddress) -> usize {
+        let mut budget = usize::MAX;
+        if self.config.send_rate_limit > 0 {
+            let rate = self.config.send_rate_limit;
+            let bucket = self.send_buckets.entry(address).or_insert_with(|| TokenBucket::create(rate));
+            bucket.refill(Instant::now());
+            budget = bucket.available();
+        }
+
+        let count = self.channel_order.len();
+        let mut deficits: Vec<i64> = vec![0; count];
+        let mut weights: Vec<u32> = vec![0; count];
+        let mut queued: Vec<bool> = vec![false; count];
+        for (i, channel_id) in self.channel_order.iter().enumerate() {
+            if let Some(channel) = self.channels.get(&(address, *channel_id)) {
+                deficits[i] = channel.deficit;
+                weights[i] = channel.weight;
+                queued[i] = channel.has_queued();
+            }
+        }
+
+        let order = &self.channel_order;
+        let channels = &mut self.channels;
+        let socket = &self.socket;
+        let sent = scheduler::schedule(&mut deficits, &weights, &mut queued, budget, |i, max_bytes| {
+            match channels.get_mut(&(address, order[i])) {
+                Some(channel) => {
+                    let sent = channel.pump_stream(socket, max_bytes);
+                    return (sent, channel.has_queued());
+                }
+                None => {
+                    return (0, false);
+                }
+            }
+        });
+
+        for (i, channel_id) in self.channel_order.iter().enumerate() {
+            if let Some(channel) = self.channels.get_mut(&(address, *channel_id)) {
+                channel.deficit = deficits[i];
+            }
+        }
+
+        if let Some(bucket) = self.send_buckets.get_mut(&address) {
+            bucket.take(sent);
+        }
+        return sent;
+    }
+
+    fn push_lost_messages(&mut self, address: NetworkAddress, channel_id: u8, count: u64) {
+        let identity = self.get_connection_identity(address).id;
+        for _ in 0..count {
+            self.push_event(Event::MessageLost { channel: channel_id, address, identity });
+        }
+    }
+
+    // over the cap the oldest event that isn't a message is dropped
+    pub(crate) fn push_event(&mut self, event: Event) {
+        if self.events.len() - self.event_messages >= MAX_QUEUED_EVENTS {
+            if let Some(index) = self.events.iter().position(|e| !matches!(e, Event::Message { .. })) {
+                self.events.remove(index);
+            }
+        }
+        self.events.push_back(event);
+    }
+
+    fn push_message_event(&mut self, event: Event) {
+        self.event_messages += 1;
+        self.events.push_back(event);
+    }
+
+    // Receives everything waiting and returns it with the connection and identity events queued since the last
+    // poll, in the order they happened.  An alternative to receive_loop and the callbacks, not to be mixed with them.
+    pub fn poll_events(&mut self) -> std::collections::vec_deque::Drain<'_, Event> {
+        let mut receive_buffer = std::mem::take(&mut self.event_receive_buffer);
+        loop {
+            let result = self.receive_loop(&mut receive_buffer);
+            if result.error == RECEIVE_ERROR_BUFFER_TOO_SMALL {
+                receive_buffer.resize(result.length as usize, 0);
+                continue;
+            }
+            if result.error == RECEIVE_ERROR_CHANNEL {
+                continue;
+            }
+            if result.length == 0 || result.error != 0 {
+                break;
+            }
+
+            let length = result.length as usize;
+            let event = Event::Message {
+                channel: result.channel as u8,
+                address: result.address,
+                identity: self.get_connection_identity(result.address).id,
+                data: receive_buffer[0..length].to_vec(),
+            };
+            self.push_message_event(event);
         }
+        self.event_receive_buffer = receive_buffer;
+        self.event_messages = 0;
+        return self.events.drain(..);
+    }
+
+    pub fn take_delivery_event(&mut self) -> Option<DeliveryEvent> {
+        return self.delivery_events.pop_front();
     }
 
-    fn receive_published_channel_id(&mut self,  receive_buffer: &mut [u8], address: NetworkAddress, channel_id: u8) -> u32 {
+    // returns length, error
+    fn receive_published_channel_id(&mut self,  receive_buffer: &mut [u8], address: NetworkAddress, channel_id: u8) -> (u32, u32) {
         match self.channels.get_mut(&(address, channel_id)) {
             Some(channel) => {
                 let res = channel.receive_published(receive_buffer);
-                return res.0;
+                return (res.0, res.2);
             }
             None => {
-                return 0;
+                return (0, 0);
             }
         }
     }
@@ -281,6 +738,7 @@ impl Tachyon {
             if res.0 > 0 {
                 result.length = res.0;
                 result.address = res.1;
+                result.error = res.2;
                 result.channel = channel.id as u16;
                 return result;
             }
@@ -288,9 +746,77 @@ impl Tachyon {
         return result;
     }
 
+    // unreliable messages are delivered from socket_receive_buffer, channel_id is 0 for ones not sent on a channel
+    fn receive_unreliable(&mut self, receive_buffer: &mut [u8], received_len: usize, address: NetworkAddress, channel_id: u8) -> TachyonReceiveResult {
+        let mut result = TachyonReceiveResult::default();
+        let mut header_size = 1;
+        if channel_id > 0 {
+            header_size = TACHYON_HEADER_SIZE;
+        }
+        let length = received_len - header_size;
+        result.address = address;
+        result.length = length as u32;
+        result.channel = channel_id as u16;
+
+        if length > receive_buffer.len() {
+            self.pending_unreliable = Some((received_len, address, channel_id));
+            result.error = RECEIVE_ERROR_BUFFER_TOO_SMALL;
+            return result;
+        }
+
+        self.pending_unreliable = None;
+        receive_buffer[0..length].copy_from_slice(&self.socket_receive_buffer[header_size..received_len]);
+        return result;
+    }
+
+    // receive_loop with errors as TachyonError, None when there is nothing to receive
+    pub fn try_receive(&mut self, receive_buffer: &mut [u8]) -> Result<Option<TachyonReceiveResult>, TachyonError> {
+        let result = self.receive_loop(receive_buffer);
+        if let Some(error) = TachyonError::from_receive_error(result.error, result.length) {
+            return Err(error);
+        }
+        if result.length == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(result));
+    }
+
+    // receive_loop that blocks on the socket until a message arrives or the timeout passes.  Doesn't run update(),
+    // call it between receives as usual.
+    pub fn receive_timeout(&mut self, receive_buffer: &mut [u8], timeout: Duration) -> TachyonReceiveResult {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let result = self.receive_loop(receive_buffer);
+            if result.length > 0 || result.error != 0 {
+                return result;
+            }
+
+            // datagrams that are not messages themselves, acks, nacks and fragments, go around again
+            let now = Instant::now();
+            if now >= deadline || !self.socket.wait_readable(deadline - now) {
+                return result;
+            }
+        }
+    }
+
+    pub fn try_receive_timeout(&mut self, receive_buffer: &mut [u8], timeout: Duration) -> Result<Option<TachyonReceiveResult>, TachyonError> {
+        let result = self.receive_timeout(receive_buffer, timeout);
+        if let Some(error) = TachyonError::from_receive_error(result.error, result.length) {
+            return Err(error);
+        }
+        if result.length == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(result));
+    }
+
     pub fn receive_loop(&mut self, receive_buffer: &mut [u8]) -> TachyonReceiveResult {
         let mut result = TachyonReceiveResult::default();
 
+        if let Some((received_len, address, channel_id)) = self.pending_unreliable {
+            return self.receive_unreliable(receive_buffer, received_len, address, channel_id);
+        }
+
         for _ in 0..100 {
             let receive_result = self.receive_from_socket();
             match receive_result {
@@ -299,9 +825,10 @@ impl Tachyon {
                     channel_id,
                 } => {
                     let published = self.receive_published_channel_id(receive_buffer, socket_addr, channel_id);
-                    if published > 0 {
+                    if published.0 > 0 {
                         result.channel = channel_id as u16;
-                        result.length = published;
+                        result.length = published.0;
+                        result.error = published.1;
                         result.address = socket_addr;
                         return result;
                     }
@@ -309,11 +836,9 @@ impl Tachyon {
                 ReceiveResult::UnReliable {
                     received_len,
                     network_address: socket_addr,
+                    channel_id,
                 } => {
-                    receive_buffer[0..received_len-1].copy_from_slice(&self.socket_receive_buffer[1..received_len]);
-                    result.length = (received_len - 1) as u32;
-                    result.address = socket_addr;
-                    return result;
+                    return self.receive_unreliable(receive_buffer, received_len, socket_addr, channel_id);
                 }
                 ReceiveResult::Empty => {
                     break;
@@ -344,6 +869,13 @@ impl Tachyon {
                 received_len = bytes_received;
                 address = network_address;
 
+                // larger then we agreed to receive.  Until settings are exchanged the other side sends up to the default,
+                // so that is always accepted even when our own size is smaller.
+                if received_len > std::cmp::max(self.config.get_max_datagram_size(), MAX_DATAGRAM_SIZE_DEFAULT) {
+                    self.stats.packets_dropped += 1;
+                    return ReceiveResult::Retry;
+                }
+
                 header = Header::read(&self.socket_receive_buffer);
 
                 if self.socket.is_server {
@@ -401,11 +933,27 @@ impl Tachyon {
             }
         }
 
+        if header.message_type == MESSAGE_TYPE_SETTINGS {
+            self.on_receive_settings(address, received_len);
+            return ReceiveResult::Retry;
+        }
+
+        if header.message_type == MESSAGE_TYPE_MTU_PROBE {
+            self.on_receive_mtu_probe(address, received_len);
+            return ReceiveResult::Retry;
+        }
+
+        if header.message_type == MESSAGE_TYPE_MTU_PROBE_ACK {
+            self.on_receive_mtu_probe_ack(address, received_len);
+            return ReceiveResult::Retry;
+        }
+
         if header.message_type == MESSAGE_TYPE_UNRELIABLE {
             self.stats.unreliable_received += 1;
             return ReceiveResult::UnReliable {
                 received_len: received_len,
                 network_address: address,
+                channel_id: 0,
             };
         }
 
@@ -418,22 +966,41 @@ impl Tachyon {
 
         channel.stats.bytes_received += received_len as u64;
 
+        if header.message_type == MESSAGE_TYPE_UNRELIABLE_CHANNEL {
+            if received_len <= TACHYON_HEADER_SIZE || !channel.process_unreliable_message(header.sequence) {
+                return ReceiveResult::Retry;
+            }
+            self.stats.unreliable_received += 1;
+            return ReceiveResult::UnReliable {
+                received_len: received_len,
+                network_address: address,
+                channel_id: header.channel,
+            };
+        }
+
         if header.message_type == MESSAGE_TYPE_NONE {
             channel.process_none_message(header.sequence, &mut self.socket_receive_buffer, received_len);
             return ReceiveResult::Retry;
         }
 
+        if header.message_type == MESSAGE_TYPE_ACK {
+            channel.process_ack_message(header.sequence, &self.socket_receive_buffer, received_len);
+            return ReceiveResult::Retry;
+        }
+
         if header.message_type == MESSAGE_TYPE_NACK {
+            channel.process_ack(header.sequence);
             channel.process_nack_message(address, &mut self.socket_receive_buffer);
             return ReceiveResult::Retry;
         }
 
         if header.message_type == MESSAGE_TYPE_FRAGMENT {
             channel.process_fragment_message(header.sequence, &mut self.socket_receive_buffer, received_len);
+            self.enforce_fragment_limit(address);
             return ReceiveResult::Retry;
         }
 
-        if header.message_type == MESSAGE_TYPE_RELIABLE || header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
+        if header.message_type == MESSAGE_TYPE_RELIABLE || header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK || header.message_type == MESSAGE_TYPE_BATCH {
 
             if header.message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
                 channel.process_single_nack(address, &mut self.socket_receive_buffer);
@@ -454,6 +1021,9 @@ impl Tachyon {
     }
 
     pub fn send_to_target(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> TachyonSendResult {
+        if target.identity_id == 0 && target.address.is_broadcast() {
+            return self.broadcast(channel, data, length, &[]).to_send_result();
+        }
         let mut address = target.address;
     
         if target.identity_id > 0 {
@@ -473,53 +1043,197 @@ impl Tachyon {
         }
     }
 
-    pub fn send_unreliable(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
-        if !self.can_send() {
-            let mut result = TachyonSendResult::default();
-            result.error = SEND_ERROR_IDENTITY;
-            return result;
-        }
-
-        match &mut self.unreliable_sender {
-            Some(sender) => {
-                let result = sender.send(address, data, body_len);
-                if result.error == 0 {
-                    self.stats.unreliable_sent += 1;
-                }
-                return result;
+    // send_to_target with errors as TachyonError, channel 0 is unreliable
+    pub fn try_send_to_target(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> Result<TachyonSendResult, TachyonError> {
+        let result = self.send_to_target(channel, target, data, length);
+        match TachyonError::from_send_error(result.error) {
+            Some(error) => {
+                return Err(error);
             }
             None => {
-                let mut result = TachyonSendResult::default();
-                result.error = SEND_ERROR_UNKNOWN;
-                return result;
+                return Ok(result);
             }
         }
     }
 
-    pub fn send_reliable(&mut self, channel_id: u8, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
-        let mut result = TachyonSendResult::default();
+    pub fn subscribe(&mut self, topic: u32, target: SendTarget) -> bool {
+        return self.topics.subscribe(topic, target);
+    }
 
-        if !self.can_send() {
-            result.error = SEND_ERROR_IDENTITY;
-            return result;
+    pub fn unsubscribe(&mut self, topic: u32, target: SendTarget) -> bool {
+        return self.topics.unsubscribe(topic, target);
+    }
+
+    pub fn unsubscribe_all(&mut self, target: SendTarget) {
+        self.topics.unsubscribe_all(target);
+    }
+
+    // Sends to every subscriber of the topic, once per connection when subscribed by both address and identity.
+    // Channel 0 sends unreliable.
+    pub fn publish(&mut self, topic: u32, channel_id: u8, data: &mut [u8], length: usize) -> PublishResult {
+        let mut result = PublishResult::default();
+        let mut addresses: Vec<NetworkAddress> = Vec::new();
+        let mut seen: FxHashSet<NetworkAddress> = FxHashSet::default();
+
+        for target in self.topics.get_subscribers(topic) {
+            let address = if target.identity_id > 0 {
+                self.identity_to_address_map.get(&target.identity_id).copied()
+            } else if self.connections.contains_key(&target.address) {
+                Some(target.address)
+            } else {
+                None
+            };
+
+            match address {
+                Some(address) => {
+                    if seen.insert(address) {
+                        addresses.push(address);
+                    }
+                }
+                None => {
+                    result.count(SEND_ERROR_UNKNOWN_TARGET, 0);
+                }
+            }
         }
 
-        if body_len == 0 {
-            result.error = SEND_ERROR_LENGTH;
+        result.merge(self.send_to_addresses(channel_id, &addresses, data, length));
+        return result;
+    }
+
+    // Sends to every connection except those matching exclude.  With identities a server only sends to linked
+    // connections.  Channel 0 sends unreliable.
+    pub fn broadcast(&mut self, channel_id: u8, data: &mut [u8], length: usize, exclude: &[SendTarget]) -> PublishResult {
+        let linked_only = self.config.use_identity == 1 && self.socket.is_server;
+        let mut addresses: Vec<NetworkAddress> = Vec::new();
+        for conn in self.connections.values() {
+            if linked_only && !conn.identity.is_linked() {
+                continue;
+            }
+            if exclude.iter().any(|target| target.matches(conn)) {
+                continue;
+            }
+            addresses.push(conn.address);
+        }
+        return self.send_to_addresses(channel_id, &addresses, data, length);
+    }
+
+    // One message to many connections.  On a compressed channel it's compressed once for all of them, after
+    // that each connection only adds it's own headers and send buffers.
+    pub fn send_to_addresses(&mut self, channel_id: u8, addresses: &[NetworkAddress], data: &mut [u8], length: usize) -> PublishResult {
+        let mut result = PublishResult::default();
+        if addresses.len() == 0 {
             return result;
         }
 
         if channel_id == 0 {
-            result.error = SEND_ERROR_CHANNEL;
+            for address in addresses {
+                let send_result = self.send_unreliable(*address, data, length);
+                result.count(send_result.error, send_result.sent_len);
+            }
             return result;
         }
 
-        if !self.socket.socket.is_some() {
-            result.error = SEND_ERROR_SOCKET;
+        let error = self.reliable_send_error(channel_id, length);
+        if error != 0 {
+            result.failed = addresses.len() as u32;
+            result.error = error;
             return result;
         }
 
-        let channel = match self.channels.get_mut(&(address, channel_id)) {
+        #[cfg(feature = "compression")]
+        let mut compressed: Option<(NetworkAddress, Vec<u8>, usize)> = None;
+        for address in addresses {
+            let send_result = match self.channels.get_mut(&(*address, channel_id)) {
+                #[cfg(feature = "compression")]
+                Some(channel) if channel.is_compressed() => {
+                    if compressed.is_none() {
+                        let (buffer, compressed_len) = channel.compress(&data[0..length]);
+                        compressed = Some((*address, buffer, compressed_len));
+                    }
+                    let (_, buffer, compressed_len) = compressed.as_mut().unwrap();
+                    let compressed_len = *compressed_len;
+                    self.send_reliable_body(channel_id, *address, buffer, compressed_len)
+                }
+                _ => self.send_reliable_body(channel_id, *address, data, length),
+            };
+            result.count(send_result.error, send_result.sent_len);
+        }
+
+        #[cfg(feature = "compression")]
+        if let Some((address, buffer, _)) = compressed {
+            if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+                channel.return_compress_buffer(buffer);
+            }
+        }
+        return result;
+    }
+
+    pub fn send_unreliable(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+        if !self.can_send() {
+            let mut result = TachyonSendResult::default();
+            result.error = SEND_ERROR_IDENTITY;
+            return result;
+        }
+
+        // + 1 for the message type
+        if body_len + 1 > self.get_max_datagram_size(address) {
+            let mut result = TachyonSendResult::default();
+            result.error = SEND_ERROR_LENGTH;
+            return result;
+        }
+
+        match &mut self.unreliable_sender {
+            Some(sender) => {
+                let result = sender.send(address, data, body_len);
+                if result.error == 0 {
+                    self.stats.unreliable_sent += 1;
+                }
+                return result;
+            }
+            None => {
+                let mut result = TachyonSendResult::default();
+                result.error = SEND_ERROR_UNKNOWN;
+                return result;
+            }
+        }
+    }
+
+    pub fn send_unreliable_to_target(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> TachyonSendResult {
+        let mut address = target.address;
+
+        if target.identity_id > 0 {
+            if let Some(addr) = self.identity_to_address_map.get(&target.identity_id) {
+                address = *addr;
+            } else {
+                let mut result = TachyonSendResult::default();
+                result.error = SEND_ERROR_IDENTITY;
+                return result;
+            }
+        }
+
+        return self.send_unreliable_channel(channel, address, data, length);
+    }
+
+    // Unreliable send tagged with a channel and that channel's unreliable sequence, so the receiver can tell
+    // streams apart and drop duplicates.  Channel 0 is a plain unreliable send.
+    pub fn send_unreliable_channel(&mut self, channel_id: u8, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+        if channel_id == 0 {
+            return self.send_unreliable(address, data, body_len);
+        }
+
+        let mut result = TachyonSendResult::default();
+
+        if !self.can_send() {
+            result.error = SEND_ERROR_IDENTITY;
+            return result;
+        }
+
+        if body_len + TACHYON_HEADER_SIZE > self.get_max_datagram_size(address) {
+            result.error = SEND_ERROR_LENGTH;
+            return result;
+        }
+
+        let channel = match self.channels.get_mut(&(address, channel_id)) {
             Some(c) => c,
             None => {
                 result.error = SEND_ERROR_CHANNEL;
@@ -527,7 +1241,139 @@ impl Tachyon {
             }
         };
 
-        if Fragmentation::should_fragment(body_len) {
+        match &mut self.unreliable_sender {
+            Some(sender) => {
+                let sequence = channel.unreliable.next_send_sequence();
+                let result = sender.send_on_channel(address, channel_id, sequence, data, body_len);
+                if result.error == 0 {
+                    self.stats.unreliable_sent += 1;
+                    channel.stats.unreliable_sent += 1;
+                }
+                return result;
+            }
+            None => {
+                result.error = SEND_ERROR_UNKNOWN;
+                return result;
+            }
+        }
+    }
+
+    // send_reliable with a ttl for just this message instead of the channel's
+    pub fn send_reliable_ttl(&mut self, channel_id: u8, address: NetworkAddress, data: &mut [u8], body_len: usize, ttl: u32) -> TachyonSendResult {
+        let (channel_ttl, notify_delivery) = match self.channels.get_mut(&(address, channel_id)) {
+            Some(channel) => {
+                let channel_ttl = channel.send_buffers.ttl;
+                let notify_delivery = channel.notify_delivery;
+                channel.send_buffers.ttl = ttl;
+                // track this message so the expired event reaches the sender
+                channel.notify_delivery = notify_delivery || ttl > 0;
+                (channel_ttl, notify_delivery)
+            }
+            None => {
+                return self.send_reliable(channel_id, address, data, body_len);
+            }
+        };
+
+        let result = self.send_reliable(channel_id, address, data, body_len);
+
+        if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+            channel.send_buffers.ttl = channel_ttl;
+            channel.notify_delivery = notify_delivery;
+        }
+        return result;
+    }
+
+    // checks that don't depend on the connection, 0 if none fail
+    fn reliable_send_error(&self, channel_id: u8, body_len: usize) -> u32 {
+        if !self.can_send() {
+            return SEND_ERROR_IDENTITY;
+        }
+
+        if body_len == 0 {
+            return SEND_ERROR_LENGTH;
+        }
+
+        if channel_id == 0 {
+            return SEND_ERROR_CHANNEL;
+        }
+
+        if body_len > self.config.get_max_message_size() {
+            return SEND_ERROR_MESSAGE_SIZE;
+        }
+
+        if !self.socket.socket.is_some() {
+            return SEND_ERROR_SOCKET;
+        }
+        return 0;
+    }
+
+    pub fn send_reliable(&mut self, channel_id: u8, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+
+        result.error = self.reliable_send_error(channel_id, body_len);
+        if result.error != 0 {
+            return result;
+        }
+
+        // compressed before anything else so fragmenting and batching see the compressed size
+        #[cfg(feature = "compression")]
+        if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+            if channel.is_compressed() {
+                let (mut compressed, compressed_len) = channel.compress(&data[0..body_len]);
+                let result = self.send_reliable_body(channel_id, address, &mut compressed, compressed_len);
+                if let Some(channel) = self.channels.get_mut(&(address, channel_id)) {
+                    channel.return_compress_buffer(compressed);
+                }
+                return result;
+            }
+        }
+
+        return self.send_reliable_body(channel_id, address, data, body_len);
+    }
+
+    fn send_reliable_body(&mut self, channel_id: u8, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+
+        let channel = match self.channels.get_mut(&(address, channel_id)) {
+            Some(c) if !c.mismatched => c,
+            _ => {
+                result.error = SEND_ERROR_CHANNEL;
+                return result;
+            }
+        };
+
+        if channel.frag.get_fragment_count(body_len) > channel.max_fragments() {
+            result.error = SEND_ERROR_MESSAGE_SIZE;
+            return result;
+        }
+
+        if !channel.can_fragment() && channel.frag.should_fragment(body_len) {
+            result.error = SEND_ERROR_MESSAGE_SIZE;
+            return result;
+        }
+
+        // not buffered, so not batched, queued or rate limited either
+        if channel.mode == CHANNEL_MODE_UNRELIABLE_SEQUENCED {
+            return channel.send_sequenced(address, data, body_len, &self.socket);
+        }
+
+        if channel.is_batching() {
+            if channel.can_batch(body_len) {
+                return channel.send_batched(data, body_len, &self.socket);
+            }
+            // keep sequences in send order
+            channel.flush_batch(&self.socket);
+        }
+
+        if channel.is_stream() {
+            let mut result = channel.send_stream(data, body_len);
+            if result.error == 0 {
+                result.sent_len = self.schedule_connection(address) as u32;
+            }
+            return result;
+        }
+
+        if channel.frag.should_fragment(body_len) {
             let mut fragment_bytes_sent = 0;
             let frag_sequences = channel.frag.create_fragments(&mut channel.send_buffers, channel.id, data, body_len);
             if frag_sequences.len() == 0 {
@@ -535,8 +1381,8 @@ impl Tachyon {
                 return result;
             }
 
-            for seq in frag_sequences {
-                match channel.send_buffers.get_send_buffer(seq) {
+            for seq in &frag_sequences {
+                match channel.send_buffers.get_send_buffer(*seq) {
                     Some(fragment) => {
                         let sent =self.socket.send_to(address, &fragment.byte_buffer.get(), fragment.byte_buffer.length);
                         fragment_bytes_sent += sent;
@@ -551,10 +1397,16 @@ impl Tachyon {
                 }
             }
 
+            let fragment_count = frag_sequences.len() as u16;
             result.header.message_type = MESSAGE_TYPE_FRAGMENT;
+            result.header.channel = channel_id;
+            result.header.sequence = frag_sequences[0];
+            result.header.fragment_start_sequence = frag_sequences[0];
+            result.header.fragment_count = fragment_count;
             result.sent_len = fragment_bytes_sent as u32;
 
             channel.stats.sent += 1;
+            channel.track_delivery(result.header.sequence, fragment_count);
 
             return result;
         }
@@ -571,6 +1423,12 @@ mod tests {
     use serial_test::serial;
 
     use crate::tachyon::tachyon_test::TachyonTest;
+    use crate::tachyon::delivery::{DELIVERY_DELIVERED, DELIVERY_EXPIRED};
+    use crate::tachyon::channel::COMPRESSION_LZ4;
+    #[cfg(feature = "compression")]
+    use crate::tachyon::channel::COMPRESSION_ZSTD;
+    use crate::tachyon::error::TachyonError;
+    use crate::tachyon::receiver::MAX_REPORTED_SKIPPED;
 
     use super::*;
 
@@ -631,6 +1489,609 @@ mod tests {
         assert_eq!(3497, res.length);
     }
 
+    #[test]
+    #[serial]
+    fn test_ack() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        let sent = test.client_send_reliable(1, 2);
+        let sequence = sent.header.sequence;
+        assert!(!test.client.is_delivered(test.client_address, 1, sequence));
+
+        let res = test.server_receive();
+        assert_eq!(2, res.length);
+
+        // server sends it's current sequence as an ack on update
+        test.server.update();
+        test.client_receive();
+        assert!(test.client.is_delivered(test.client_address, 1, sequence));
+
+        let channel = test.client.get_channel(test.client_address, 1).unwrap();
+        assert!(!channel.send_buffers.is_buffered(sequence));
+        assert_eq!(1, channel.stats.acks_received);
+    }
+
+    #[test]
+    #[serial]
+    fn test_ack_skipped() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        let skipped = test.client_send_reliable(1, 2).header.sequence;
+        let received = test.client_send_reliable(1, 2).header.sequence;
+        test.server_receive();
+        test.server_receive();
+
+        // as if the receive window moved past it before it arrived
+        let remote = test.remote_client();
+        test.server.get_channel(remote, 1).unwrap().receiver.skipped.push_back(skipped);
+        test.server.update();
+        test.client_receive();
+        assert!(!test.client.is_delivered(test.client_address, 1, skipped));
+        assert!(test.client.is_delivered(test.client_address, 1, received));
+
+        // more then fit in one ack datagram
+        let mut sequences: Vec<u16> = Vec::new();
+        for _ in 0..MAX_REPORTED_SKIPPED * 2 + 1 {
+            sequences.push(test.client_send_reliable(1, 2).header.sequence);
+        }
+        for _ in 0..sequences.len() {
+            test.server_receive();
+        }
+        let channel = test.server.get_channel(remote, 1).unwrap();
+        channel.receiver.skipped.clear();
+        channel.receiver.skipped.extend(&sequences[0..MAX_REPORTED_SKIPPED * 2]);
+        test.server.update();
+        test.client_receive();
+        for sequence in &sequences[0..MAX_REPORTED_SKIPPED * 2] {
+            assert!(!test.client.is_delivered(test.client_address, 1, *sequence));
+        }
+        assert!(test.client.is_delivered(test.client_address, 1, sequences[MAX_REPORTED_SKIPPED * 2]));
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_datagram_size() {
+        let mut test = TachyonTest::default();
+        test.client.config.max_datagram_size = 8000;
+        test.server.config.max_datagram_size = 4000;
+        test.connect();
+
+        // default until settings are exchanged
+        let sent = test.client_send_reliable(1, 3000);
+        assert_eq!(MESSAGE_TYPE_FRAGMENT, sent.header.message_type);
+        let res = test.server_receive();
+        assert_eq!(3000, res.length);
+
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.settings_confirmed);
+        assert_eq!(4000, test.client.get_max_datagram_size(test.client_address));
+        let remote = test.remote_client();
+        assert_eq!(4000, test.server.get_max_datagram_size(remote));
+
+        let sent = test.client_send_reliable(1, 3900);
+        assert_eq!(MESSAGE_TYPE_RELIABLE, sent.header.message_type);
+        let res = test.server_receive();
+        assert_eq!(3900, res.length);
+
+        let sent = test.client_send_unreliable(4000);
+        assert_eq!(SEND_ERROR_LENGTH, sent.error);
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_datagram_size_below_default() {
+        let mut test = TachyonTest::default();
+        test.server.config.max_datagram_size = 512;
+        test.connect();
+
+        // sent at the default size before the client knows the server's
+        let sent = test.client_send_reliable(1, 1000);
+        assert_eq!(MESSAGE_TYPE_RELIABLE, sent.header.message_type);
+        assert_eq!(1000, test.server_receive().length);
+
+        test.client.update();
+        test.server_receive();
+        test.client_receive();
+        assert!(test.client.settings_confirmed);
+        assert_eq!(512, test.client.get_max_datagram_size(test.client_address));
+
+        let sent = test.client_send_reliable(1, 1000);
+        assert_eq!(MESSAGE_TYPE_FRAGMENT, sent.header.message_type);
+        assert_eq!(1000, test.server_receive().length);
+    }
+
+    #[test]
+    #[serial]
+    fn test_mtu_discovery() {
+        let mut test = TachyonTest::default();
+        test.client.config.max_datagram_size = 8000;
+        test.client.config.use_mtu_discovery = 1;
+        test.server.config.max_datagram_size = 8000;
+        test.connect();
+
+        for _ in 0..100 {
+            test.client.update();
+            test.server_receive();
+            test.client_receive();
+            let conn = test.client.get_connection(test.client_address).unwrap();
+            if conn.mtu > 0 {
+                break;
+            }
+        }
+
+        // everything fits over loopback
+        let conn = *test.client.get_connection(test.client_address).unwrap();
+        assert!(conn.mtu > 8000 - 16);
+        assert!(test.client.mtu_discovery.is_empty());
+
+        let channel = test.client.get_channel(test.client_address, 1).unwrap();
+        assert_eq!(Fragmentation::fragment_size_for(conn.mtu as usize), channel.frag.fragment_size);
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_fragment_bytes() {
+        let mut test = TachyonTest::default();
+        test.server.config.max_fragment_bytes = 3000;
+        test.connect();
+
+        // both groups are received before either is assembled, the first is evicted
+        let evicted = test.client_send_reliable(1, 2500).header.sequence;
+        let assembled = test.client_send_reliable(1, 2500).header.sequence;
+        let res = test.server_receive();
+        assert_eq!(2500, res.length);
+        let res = test.server_receive();
+        assert_eq!(0, res.length);
+
+        let stats = test.server.get_combined_stats();
+        assert_eq!(1, stats.channel_stats.fragment_groups_evicted);
+        let remote = test.remote_client();
+        assert_eq!(0, test.server.get_fragment_bytes(remote));
+        assert!(test.server.events.contains(&Event::MessageLost { channel: 1, address: remote, identity: 0 }));
+
+        // reported as skipped, the sender doesn't see it delivered
+        test.server.update();
+        test.client_receive();
+        assert!(!test.client.is_delivered(test.client_address, 1, evicted));
+        assert!(test.client.is_delivered(test.client_address, 1, assembled));
+    }
+
+    #[test]
+    #[serial]
+    fn test_max_message_size() {
+        let mut test = TachyonTest::default();
+        test.client.config.max_message_size = 8000;
+        test.connect();
+        test.send_buffer = vec![0; 600 * 1024];
+
+        let sent = test.client_send_reliable(1, 8001);
+        assert_eq!(SEND_ERROR_MESSAGE_SIZE, sent.error);
+
+        // more fragments then fit in half the receive window
+        test.client.config.max_message_size = 0;
+        let sent = test.client_send_reliable(1, 600 * 1024);
+        assert_eq!(SEND_ERROR_MESSAGE_SIZE, sent.error);
+
+        let sent = test.client_send_reliable(1, 8000);
+        assert_eq!(0, sent.error);
+    }
+
+    #[test]
+    #[serial]
+    fn test_stream_channel() {
+        let mut test = TachyonTest::default();
+        let mut channel_config = ChannelConfig::default_ordered();
+        channel_config.stream = 1;
+        test.client.configure_channel(3, channel_config);
+        test.server.configure_channel(3, channel_config);
+        test.connect();
+
+        let message_len = 600 * 1024;
+        test.send_buffer = vec![0; message_len];
+        test.receive_buffer = vec![0; message_len];
+        test.send_buffer[message_len - 1] = 7;
+
+        let sent = test.client_send_reliable(3, message_len);
+        assert_eq!(0, sent.error);
+        assert_eq!(MESSAGE_TYPE_FRAGMENT, sent.header.message_type);
+        let fragment_count = sent.header.fragment_count as u64;
+
+        // only the window went out
+        let channel = test.client.get_channel(test.client_address, 3).unwrap();
+        assert_eq!(channel.window as u64, channel.stats.fragments_sent);
+        assert_eq!(1, channel.stream.as_ref().unwrap().len());
+
+        let mut received = 0;
+        for _ in 0..100 {
+            test.client.update();
+            for _ in 0..10 {
+                let res = test.server_receive();
+                if res.length > 0 {
+                    received = res.length;
+                }
+            }
+            test.server.update();
+            test.client_receive();
+            if received > 0 {
+                break;
+            }
+        }
+
+        assert_eq!(message_len as u32, received);
+        assert_eq!(7, test.receive_buffer[message_len - 1]);
+        let channel = test.client.get_channel(test.client_address, 3).unwrap();
+        assert_eq!(fragment_count, channel.stats.fragments_sent);
+        assert_eq!(0, channel.stream.as_ref().unwrap().len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_modes() {
+        let mut test = TachyonTest::default();
+        let mut sequenced = ChannelConfig::default_unordered();
+        sequenced.mode = CHANNEL_MODE_UNRELIABLE_SEQUENCED;
+        let mut latest = ChannelConfig::default_unordered();
+        latest.mode = CHANNEL_MODE_RELIABLE_LATEST;
+        for tachyon in [&mut test.client, &mut test.server] {
+            tachyon.configure_channel(3, sequenced);
+            tachyon.configure_channel(4, latest);
+        }
+        test.connect();
+
+        // can't be fragmented
+        assert_eq!(SEND_ERROR_MESSAGE_SIZE, test.client_send_reliable(3, 2000).error);
+        assert_eq!(SEND_ERROR_MESSAGE_SIZE, test.client_send_reliable(4, 2000).error);
+
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            assert_eq!(0, test.client_send_reliable(3, 32).error);
+        }
+        for i in 0..3 {
+            let res = test.server_receive();
+            assert_eq!(32, res.length);
+            assert_eq!(i, test.receive_buffer[0]);
+        }
+
+        // no acks for sequenced, and nothing buffered to resend
+        test.server.update();
+        test.client_receive();
+        let remote = test.remote_client();
+        assert_eq!(0, test.server.get_channel(remote, 3).unwrap().stats.acks_sent);
+        let channel = test.client.get_channel(test.client_address, 3).unwrap();
+        assert_eq!(0, channel.send_buffers.current_sequence);
+        assert!(!channel.is_delivered(1));
+
+        // newest latest message is resent until acked
+        test.send_buffer[0] = 9;
+        test.client_send_reliable(4, 32);
+        std::thread::sleep(std::time::Duration::from_millis(110));
+        test.client.update();
+        assert_eq!(1, test.client.get_channel(test.client_address, 4).unwrap().stats.resent);
+
+        let res = test.server_receive();
+        assert_eq!(32, res.length);
+        assert_eq!(9, test.receive_buffer[0]);
+        assert_eq!(0, test.server_receive().length);
+
+        test.server.update();
+        test.client_receive();
+        std::thread::sleep(std::time::Duration::from_millis(110));
+        test.client.update();
+        assert_eq!(1, test.client.get_channel(test.client_address, 4).unwrap().stats.resent);
+    }
+
+    #[test]
+    #[serial]
+    fn test_batch_latest() {
+        let mut test = TachyonTest::default();
+        let mut latest = ChannelConfig::default_unordered();
+        latest.mode = CHANNEL_MODE_RELIABLE_LATEST;
+        latest.batch = 1;
+        for tachyon in [&mut test.client, &mut test.server] {
+            tachyon.configure_channel(3, latest);
+        }
+        test.connect();
+
+        // sent one at a time without waiting for update
+        for i in 0..3 {
+            test.send_buffer[0] = i;
+            assert_eq!(0, test.client_send_reliable(3, 32).error);
+        }
+        for i in 0..3 {
+            assert_eq!(32, test.server_receive().length);
+            assert_eq!(i, test.receive_buffer[0]);
+        }
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(0, test.client.get_channel(test.client_address, 3).unwrap().stats.batched);
+    }
+
+    #[test]
+    #[serial]
+    fn test_batch() {
+        let mut test = TachyonTest::default();
+        let mut config = ChannelConfig::default_ordered();
+        config.batch = 1;
+        test.client.configure_channel(3, config);
+        test.server.configure_channel(3, ChannelConfig::default_ordered());
+        test.connect();
+
+        for i in 0..10 {
+            test.send_buffer[0] = i;
+            assert_eq!(0, test.client_send_reliable(3, 100).error);
+        }
+        // nothing sent until update
+        assert_eq!(0, test.server_receive().length);
+
+        // too big for the batch, flushes it first so order is kept
+        test.send_buffer[0] = 10;
+        test.client_send_reliable(3, 1200);
+        test.send_buffer[0] = 11;
+        test.client_send_reliable(3, 100);
+        test.client.update();
+
+        for i in 0..12 {
+            let res = test.server_receive();
+            assert_eq!(i, test.receive_buffer[0]);
+            if i == 10 {
+                assert_eq!(1200, res.length);
+            } else {
+                assert_eq!(100, res.length);
+            }
+        }
+        assert_eq!(0, test.server_receive().length);
+
+        let channel = test.client.get_channel(test.client_address, 3).unwrap();
+        assert_eq!(11, channel.stats.batched);
+        assert_eq!(3, channel.stats.sent);
+
+        // messages with a ttl are batched apart from ones without
+        test.client.send_reliable_ttl(3, test.client_address, &mut test.send_buffer, 100, 500);
+        test.client_send_reliable(3, 100);
+        test.client.update();
+        let channel = test.client.get_channel(test.client_address, 3).unwrap();
+        let sequence = channel.send_buffers.current_sequence;
+        assert_eq!(500, channel.send_buffers.get_send_buffer(sequence - 1).unwrap().ttl);
+        assert_eq!(0, channel.send_buffers.get_send_buffer(sequence).unwrap().ttl);
+        assert_eq!(0, channel.send_buffers.ttl);
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(feature = "compression")]
+    fn test_compression() {
+        let mut test = TachyonTest::default();
+        let dictionary: Vec<u8> = (0..512).map(|i| (i % 32) as u8).collect();
+        let mut lz4 = ChannelConfig::default_ordered();
+        lz4.compression = COMPRESSION_LZ4;
+        let mut zstd = ChannelConfig::default_ordered();
+        zstd.compression = COMPRESSION_ZSTD;
+        test.client.configure_channel(3, lz4);
+        for tachyon in [&mut test.client, &mut test.server] {
+            tachyon.configure_channel(4, zstd);
+            tachyon.set_compression_dictionary(4, &dictionary);
+        }
+        test.connect();
+        // server gets channel 3 with compression from the client's settings
+        test.client.update();
+        test.server_receive();
+
+        for i in 0..3000 {
+            test.send_buffer[i] = (i % 32) as u8;
+        }
+        for channel_id in [3, 4] {
+            assert_eq!(0, test.client_send_reliable(channel_id, 3000).error);
+            let res = test.server_receive();
+            assert_eq!(3000, res.length);
+            assert_eq!(channel_id as u16, res.channel);
+            assert_eq!(test.send_buffer[0..3000], test.receive_buffer[0..3000]);
+
+            let stats = test.client.get_channel(test.client_address, channel_id).unwrap().stats;
+            assert_eq!(3000, stats.compress_raw_bytes);
+            assert!(stats.compress_wire_bytes < 300);
+            assert_eq!(0, stats.fragments_sent);
+        }
+
+        // too small receive buffer leaves the message for the next receive
+        test.client_send_reliable(3, 3000);
+        let mut small: Vec<u8> = vec![0; 100];
+        let res = test.server.receive_loop(&mut small);
+        assert_eq!(RECEIVE_ERROR_BUFFER_TOO_SMALL, res.error);
+        assert_eq!(3000, res.length);
+        assert_eq!(3000, test.server_receive().length);
+
+        // a null dictionary from the ffi clears it
+        crate::tachyon::ffi::set_compression_dictionary(&mut test.client, 4, std::ptr::null(), 0);
+        assert!(!test.client.compression_dictionaries.contains_key(&4));
+        assert!(!test.client.get_channel(test.client_address, 4).unwrap().compression.has_dictionary());
+    }
+
+    #[test]
+    #[serial]
+    fn test_try_api() {
+        let mut test = TachyonTest::default();
+        match test.server.try_send_to_target(1, SendTarget { address: test.address, identity_id: 0 }, &mut test.send_buffer, 32) {
+            Err(TachyonError::NotConnected) => {}
+            _ => panic!("expected NotConnected"),
+        }
+        test.connect();
+
+        let mut second = Tachyon::create(TachyonConfig::default());
+        match second.try_bind(test.address) {
+            Err(TachyonError::Socket(_)) => {}
+            _ => panic!("expected Socket"),
+        }
+
+        let target = SendTarget { address: test.client_address, identity_id: 0 };
+        assert!(test.client.try_send_to_target(1, target, &mut test.send_buffer, 500).is_ok());
+        match test.client.try_send_to_target(9, target, &mut test.send_buffer, 500) {
+            Err(TachyonError::UnknownChannel) => {}
+            _ => panic!("expected UnknownChannel"),
+        }
+        match test.client.try_send_to_target(1, SendTarget { address: test.client_address, identity_id: 7 }, &mut test.send_buffer, 500) {
+            Err(TachyonError::IdentityNotLinked) => {}
+            _ => panic!("expected IdentityNotLinked"),
+        }
+
+        let mut small: Vec<u8> = vec![0; 100];
+        match test.server.try_receive(&mut small) {
+            Err(TachyonError::BufferTooSmall { needed }) => assert_eq!(500, needed),
+            _ => panic!("expected BufferTooSmall"),
+        }
+        assert_eq!(500, test.server.try_receive(&mut test.receive_buffer).unwrap().unwrap().length);
+        assert!(test.server.try_receive(&mut test.receive_buffer).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_poll_events() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        test.client.poll_events();
+
+        // larger then the starting event buffer
+        test.send_buffer.resize(5000, 0);
+        test.send_buffer[0] = 4;
+        test.client_send_reliable(1, 5000);
+        test.send_buffer[0] = 5;
+        test.client_send_unreliable(32);
+
+        let events: Vec<Event> = test.server.poll_events().collect();
+        let remote = test.remote_client();
+        assert_eq!(3, events.len());
+        assert_eq!(Event::Connected { address: remote, identity: 0 }, events[0]);
+        for event in &events[1..] {
+            match event {
+                Event::Message { channel: 1, address, data, .. } => {
+                    assert_eq!(remote, *address);
+                    assert_eq!(5000, data.len());
+                    assert_eq!(4, data[0]);
+                }
+                Event::Message { channel: 0, data, .. } => {
+                    assert_eq!(32, data.len());
+                    assert_eq!(5, data[0]);
+                }
+                _ => panic!("expected a message"),
+            }
+        }
+        assert_eq!(0, test.server.poll_events().count());
+
+        // only events other then messages are capped
+        let message = Event::Message { channel: 1, address: remote, identity: 0, data: vec![1] };
+        let connected = Event::Connected { address: remote, identity: 0 };
+        for _ in 0..MAX_QUEUED_EVENTS {
+            test.server.push_message_event(message.clone());
+        }
+        for _ in 0..MAX_QUEUED_EVENTS + 1 {
+            test.server.push_event(connected.clone());
+        }
+        assert_eq!(MAX_QUEUED_EVENTS * 2, test.server.events.len());
+        assert!(test.server.events.iter().take(MAX_QUEUED_EVENTS).all(|e| *e == message));
+        test.server.poll_events();
+    }
+
+    #[test]
+    #[serial]
+    fn test_receive_timeout() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        let start = Instant::now();
+        assert_eq!(0, test.server.receive_timeout(&mut test.receive_buffer, Duration::from_millis(50)).length);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let address = test.address;
+        let sender = std::thread::spawn(move || {
+            let mut client = Tachyon::create(TachyonConfig::default());
+            client.connect(address);
+            std::thread::sleep(Duration::from_millis(20));
+            let mut data: Vec<u8> = vec![0; 2000];
+            let target = SendTarget { address: NetworkAddress::default(), identity_id: 0 };
+            client.send_to_target(1, target, &mut data, 2000);
+            std::thread::sleep(Duration::from_millis(100));
+        });
+
+        let start = Instant::now();
+        let res = test.server.receive_timeout(&mut test.receive_buffer, Duration::from_millis(2000));
+        assert_eq!(2000, res.length);
+        assert!(start.elapsed() < Duration::from_millis(1000));
+        sender.join().unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_receive_buffer_too_small() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        test.send_buffer[1999] = 5;
+        test.client_send_reliable(1, 2000);
+        test.client_send_reliable(1, 200);
+
+        // message stays queued until there is room for it
+        let mut small_buffer: Vec<u8> = vec![0; 100];
+        for _ in 0..2 {
+            let res = test.server.receive_loop(&mut small_buffer);
+            assert_eq!(RECEIVE_ERROR_BUFFER_TOO_SMALL, res.error);
+            assert_eq!(2000, res.length);
+        }
+
+        let res = test.server_receive();
+        assert_eq!(0, res.error);
+        assert_eq!(2000, res.length);
+        assert_eq!(5, test.receive_buffer[1999]);
+
+        let res = test.server.receive_loop(&mut small_buffer);
+        assert_eq!(RECEIVE_ERROR_BUFFER_TOO_SMALL, res.error);
+        assert_eq!(200, res.length);
+        let res = test.server_receive();
+        assert_eq!(200, res.length);
+
+        test.client_send_unreliable(500);
+        let res = test.server.receive_loop(&mut small_buffer);
+        assert_eq!(RECEIVE_ERROR_BUFFER_TOO_SMALL, res.error);
+        assert_eq!(500, res.length);
+        let res = test.server_receive();
+        assert_eq!(0, res.error);
+        assert_eq!(500, res.length);
+    }
+
+    #[test]
+    #[serial]
+    fn test_delivery_events() {
+        let mut test = TachyonTest::default();
+        let mut channel_config = ChannelConfig::default_ordered();
+        channel_config.notify_delivery = 1;
+        test.client.configure_channel(3, channel_config);
+        test.server.configure_channel(3, channel_config);
+        test.connect();
+
+        let sent = test.client_send_reliable(3, 2);
+        let fragmented = test.client_send_reliable(3, 3000);
+        assert_eq!(3, fragmented.header.fragment_count);
+
+        test.server_receive();
+        test.server_receive();
+        test.server.update();
+        test.client_receive();
+        test.client.update();
+
+        let event = test.client.take_delivery_event().unwrap();
+        assert_eq!(DELIVERY_DELIVERED, event.status);
+        assert_eq!(3, event.channel);
+        assert_eq!(sent.header.sequence, event.sequence);
+
+        let event = test.client.take_delivery_event().unwrap();
+        assert_eq!(DELIVERY_DELIVERED, event.status);
+        assert_eq!(fragmented.header.sequence, event.sequence);
+        assert_eq!(3, event.fragment_count);
+
+        assert!(test.client.take_delivery_event().is_none());
+    }
+
     #[test]
     #[serial]
     fn test_unconfigured_channel_fails() {
@@ -696,4 +2157,228 @@ mod tests {
         assert_eq!(5, test.receive_buffer[2]);
         assert_eq!(6, test.receive_buffer[3]);
     }
+
+    #[test]
+    #[serial]
+    fn test_unreliable_channel() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        assert_eq!(SEND_ERROR_CHANNEL, test.client_send_unreliable_channel(9, 4).error);
+
+        test.send_buffer[0] = 3;
+        test.send_buffer[3] = 6;
+        let sent = test.client_send_unreliable_channel(2, 4);
+        assert_eq!(0, sent.error);
+        assert_eq!(8, sent.sent_len);
+        assert_eq!(1, sent.header.sequence);
+        assert_eq!(2, test.client_send_unreliable_channel(2, 4).header.sequence);
+        assert_eq!(1, test.client_send_unreliable_channel(1, 4).header.sequence);
+
+        let res = test.server_receive();
+        assert_eq!(4, res.length);
+        assert_eq!(2, res.channel);
+        assert_eq!(3, test.receive_buffer[0]);
+        assert_eq!(6, test.receive_buffer[3]);
+        assert_eq!(2, test.server_receive().channel);
+        assert_eq!(1, test.server_receive().channel);
+
+        // duplicate is dropped
+        let remote = test.remote_client();
+        test.server.get_channel(remote, 2).unwrap().unreliable.newest_received = 0;
+        test.client.get_channel(test.client_address, 2).unwrap().unreliable.send_sequence = 1;
+        test.client_send_unreliable_channel(2, 4);
+        assert_eq!(0, test.server_receive().length);
+        assert_eq!(1, test.server.get_channel(remote, 2).unwrap().unreliable.dropped);
+
+        // channel 0 is a plain unreliable message
+        let sent = test.client_send_unreliable_channel(0, 4);
+        assert_eq!(5, sent.sent_len);
+        assert_eq!(0, test.server_receive().channel);
+    }
+
+    #[test]
+    #[serial]
+    fn test_send_rate_limit() {
+        let mut test = TachyonTest::default();
+        test.client.config.send_rate_limit = 10000;
+        let mut high = ChannelConfig::default_unordered();
+        high.priority = 10;
+        high.weight = 3;
+        test.client.configure_channel(3, high);
+        test.client.configure_channel(4, ChannelConfig::default_unordered());
+        assert_eq!(3, test.client.channel_order[0]);
+        test.server.configure_channel(3, high);
+        test.server.configure_channel(4, ChannelConfig::default_unordered());
+        test.connect();
+
+        // bucket starts with 1024 bytes
+        for _ in 0..10 {
+            assert_eq!(0, test.client_send_reliable(4, 500).error);
+        }
+        assert_eq!(3, test.client.get_channel(test.client_address, 4).unwrap().stats.sent);
+
+        // queued behind the limit, the higher priority channel gets the budget first once it refills
+        for _ in 0..10 {
+            test.client_send_reliable(3, 500);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(110));
+        test.client.update();
+        assert!(test.client.get_channel(test.client_address, 3).unwrap().stats.sent > 0);
+        assert_eq!(3, test.client.get_channel(test.client_address, 4).unwrap().stats.sent);
+
+        let mut received = 0;
+        for _ in 0..40 {
+            std::thread::sleep(std::time::Duration::from_millis(110));
+            test.client.update();
+            for _ in 0..10 {
+                if test.server_receive().length > 0 {
+                    received += 1;
+                }
+            }
+            if received == 20 {
+                break;
+            }
+        }
+        assert_eq!(20, received);
+    }
+
+    #[test]
+    #[serial]
+    fn test_ttl() {
+        let mut test = TachyonTest::default();
+        test.connect();
+
+        // first message is lost
+        let sent = test.client.send_reliable_ttl(1, test.client_address, &mut test.send_buffer, 32, 100);
+        assert_eq!(0, sent.error);
+        let mut lost: Vec<u8> = vec![0; 4096];
+        assert!(matches!(test.server.socket.receive(&mut lost, 0, false), SocketReceiveResult::Success { .. }));
+
+        // ordered channel waits on it
+        test.send_buffer[0] = 5;
+        test.client_send_reliable(1, 32);
+        assert_eq!(0, test.server_receive().length);
+
+        std::thread::sleep(std::time::Duration::from_millis(110));
+        test.client.update();
+        let channel = test.client.get_channel(test.client_address, 1).unwrap();
+        assert_eq!(1, channel.stats.expired);
+        assert_eq!(0, channel.send_buffers.ttl);
+        assert!(!channel.notify_delivery);
+
+        let event = test.client.take_delivery_event().unwrap();
+        assert_eq!(DELIVERY_EXPIRED, event.status);
+        assert_eq!(sent.header.sequence, event.sequence);
+
+        // the none lets the second one through
+        let res = test.server_receive();
+        assert_eq!(32, res.length);
+        assert_eq!(5, test.receive_buffer[0]);
+
+        // the ack covers the none, but it was never delivered
+        test.server.update();
+        test.client_receive();
+        test.client.update();
+        assert!(!test.client.is_delivered(test.client_address, 1, sent.header.sequence));
+        assert!(test.client.is_delivered(test.client_address, 1, sent.header.sequence + 1));
+        assert!(test.client.take_delivery_event().is_none());
+
+        // events nobody takes are capped
+        let channel = test.client.get_channel(test.client_address, 1).unwrap();
+        for _ in 0..MAX_QUEUED_EVENTS + 1 {
+            channel.delivery.events.push_back(DeliveryEvent::default());
+        }
+        test.client.update();
+        assert_eq!(MAX_QUEUED_EVENTS, test.client.delivery_events.len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_channel_negotiation() {
+        let mut test = TachyonTest::default();
+        test.client.configure_channel(3, ChannelConfig::default_ordered());
+        test.server.configure_channel(4, ChannelConfig::default_unordered());
+        let mut sequenced = ChannelConfig::default_unordered();
+        sequenced.mode = CHANNEL_MODE_UNRELIABLE_SEQUENCED;
+        test.client.configure_channel(5, ChannelConfig::default_ordered());
+        test.server.configure_channel(5, sequenced);
+        test.connect();
+
+        for _ in 0..2 {
+            test.client.update();
+            test.server_receive();
+            test.client_receive();
+        }
+        assert!(test.client.settings_confirmed);
+
+        let remote = test.remote_client();
+        assert!(test.server.get_channel(remote, 3).unwrap().is_ordered());
+        assert!(test.client.get_channel(test.client_address, 4).is_some());
+
+        let mismatch = test.server.take_channel_mismatch().unwrap();
+        assert_eq!(5, mismatch.channel);
+        assert_eq!(CHANNEL_MODE_UNRELIABLE_SEQUENCED as u8, mismatch.local_mode);
+        assert_eq!(1, mismatch.remote_ordered);
+        assert_eq!(5, test.client.take_channel_mismatch().unwrap().channel);
+
+        // unusable on both sides
+        assert_eq!(SEND_ERROR_CHANNEL, test.client_send_reliable(5, 32).error);
+        assert_eq!(SEND_ERROR_CHANNEL, test.server_send_reliable(5, 32).error);
+
+        assert_eq!(0, test.client_send_reliable(3, 32).error);
+        assert_eq!(3, test.server_receive().channel);
+
+        // live channels can't be reconfigured, but can be added and removed
+        assert!(!test.server.configure_channel(4, ChannelConfig::default_ordered()));
+        assert!(test.server.configure_channel(6, ChannelConfig::default_ordered()));
+        assert!(test.server.get_channel(remote, 6).is_some());
+        assert!(test.server.remove_channel(4));
+        assert!(test.server.get_channel(remote, 4).is_none());
+
+        // both announced, last one wins
+        test.client_receive();
+        test.client_receive();
+        assert!(test.client.get_channel(test.client_address, 6).is_some());
+        assert!(test.client.get_channel(test.client_address, 4).is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_publish() {
+        let mut test = TachyonTest::default();
+        let mut lz4 = ChannelConfig::default_ordered();
+        lz4.compression = COMPRESSION_LZ4;
+        for tachyon in [&mut test.client, &mut test.server] {
+            tachyon.configure_channel(3, lz4);
+        }
+        test.connect();
+        test.client_send_reliable(1, 32);
+        test.server_receive();
+        let remote = test.remote_client();
+
+        // by address twice and by an unknown identity, sent once
+        assert!(test.server.subscribe(5, SendTarget { address: remote, identity_id: 0 }));
+        assert!(!test.server.subscribe(5, SendTarget { address: remote, identity_id: 0 }));
+        assert!(test.server.subscribe(5, SendTarget { address: NetworkAddress::default(), identity_id: 9 }));
+
+        for i in 0..3000 {
+            test.send_buffer[i] = (i % 32) as u8;
+        }
+        let result = test.server.publish(5, 3, &mut test.send_buffer, 3000);
+        assert_eq!(1, result.sent);
+        assert_eq!(1, result.failed);
+        assert_eq!(SEND_ERROR_UNKNOWN_TARGET, result.error);
+        #[cfg(feature = "compression")]
+        assert!(test.server.get_channel(remote, 3).unwrap().stats.compress_wire_bytes < 300);
+
+        let res = test.client_receive();
+        assert_eq!(3000, res.length);
+        assert_eq!(3, res.channel);
+        assert_eq!(test.send_buffer[0..3000], test.receive_buffer[0..3000]);
+
+        assert_eq!(1, test.server.publish(5, 0, &mut test.send_buffer, 100).sent);
+        assert_eq!(100, test.client_receive().length);
+        assert_eq!(0, test.server.publish(6, 1, &mut test.send_buffer, 100).sent);
+    }
 }
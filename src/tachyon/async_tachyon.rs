@@ -0,0 +1,263 @@
+use std::{
+    future::poll_fn,
+    io,
+    sync::{Arc, Mutex},
+    task::Poll,
+    time::Duration,
+};
+
+use tokio::{io::Interest, net::UdpSocket, task::JoinHandle};
+
+use super::{
+    error::TachyonError, event::Event, pool::{Pool, SendTarget}, receive_result::TachyonReceiveResult, Tachyon,
+    TachyonSendResult,
+};
+
+// Async wrappers for tokio.  Tachyon itself is unchanged, it's kept behind a lock and reads from it's own
+// non blocking socket.  A clone of that socket is registered with the reactor only to wait for readiness.
+// update() runs on an interval task until the wrapper is dropped.  Create them from inside a runtime after
+// bind or connect.
+
+fn register(tachyon: &Tachyon) -> io::Result<UdpSocket> {
+    match tachyon.socket.clone_socket() {
+        Some(socket) => {
+            return UdpSocket::from_std(socket);
+        }
+        None => {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "bind or connect first"));
+        }
+    }
+}
+
+// Readiness has to be cleared before reading, a datagram that arrives after the read then sets it again
+// and wakes the next wait.
+fn clear_readiness(socket: &UdpSocket) {
+    let _ = socket.try_io(Interest::READABLE, || Err::<(), io::Error>(io::ErrorKind::WouldBlock.into()));
+}
+
+async fn readable(sockets: &[UdpSocket]) {
+    poll_fn(|cx| {
+        for socket in sockets {
+            if socket.poll_recv_ready(cx).is_ready() {
+                return Poll::Ready(());
+            }
+        }
+        return Poll::Pending;
+    }).await;
+}
+
+pub struct AsyncTachyon {
+    tachyon: Arc<Mutex<Tachyon>>,
+    socket: UdpSocket,
+    update_task: JoinHandle<()>,
+}
+
+impl AsyncTachyon {
+    pub fn create(tachyon: Tachyon, update_interval: Duration) -> io::Result<Self> {
+        let socket = register(&tachyon)?;
+        let tachyon = Arc::new(Mutex::new(tachyon));
+
+        let update_tachyon = tachyon.clone();
+        let update_task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(update_interval);
+            loop {
+                interval.tick().await;
+                if let Ok(mut tachyon) = update_tachyon.lock() {
+                    tachyon.update();
+                }
+            }
+        });
+
+        let async_tachyon = AsyncTachyon {
+            tachyon,
+            socket,
+            update_task,
+        };
+        return Ok(async_tachyon);
+    }
+
+    // waits for the next message, errors as in Tachyon::try_receive
+    pub async fn recv(&self, receive_buffer: &mut [u8]) -> Result<TachyonReceiveResult, TachyonError> {
+        loop {
+            clear_readiness(&self.socket);
+            if let Some(result) = self.with(|tachyon| tachyon.try_receive(receive_buffer))? {
+                return Ok(result);
+            }
+            readable(std::slice::from_ref(&self.socket)).await;
+        }
+    }
+
+    // waits for the next events, see Tachyon::poll_events
+    pub async fn recv_events(&self) -> Vec<Event> {
+        loop {
+            clear_readiness(&self.socket);
+            let events: Vec<Event> = self.with(|tachyon| tachyon.poll_events().collect());
+            if events.len() > 0 {
+                return events;
+            }
+            readable(std::slice::from_ref(&self.socket)).await;
+        }
+    }
+
+    // udp sends don't block, this is async so callers don't have to care
+    pub async fn send(&self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> Result<TachyonSendResult, TachyonError> {
+        return self.with(|tachyon| tachyon.try_send_to_target(channel, target, data, length));
+    }
+
+    // anything else, don't hold on to the Tachyon across an await
+    pub fn with<R, F>(&self, f: F) -> R where F: FnOnce(&mut Tachyon) -> R {
+        let mut tachyon = match self.tachyon.lock() {
+            Ok(tachyon) => tachyon,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        return f(&mut tachyon);
+    }
+}
+
+impl Drop for AsyncTachyon {
+    fn drop(&mut self) {
+        self.update_task.abort();
+    }
+}
+
+pub struct AsyncPool {
+    pool: Arc<Mutex<Pool>>,
+    sockets: Vec<UdpSocket>,
+    update_task: JoinHandle<()>,
+}
+
+impl AsyncPool {
+    // servers have to be created before, ones added later are not waited on
+    pub fn create(pool: Pool, update_interval: Duration) -> io::Result<Self> {
+        let mut sockets: Vec<UdpSocket> = Vec::new();
+        for server in pool.servers.values() {
+            sockets.push(register(server)?);
+        }
+        let pool = Arc::new(Mutex::new(pool));
+
+        let update_pool = pool.clone();
+        let update_task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(update_interval);
+            loop {
+                interval.tick().await;
+                if let Ok(mut pool) = update_pool.lock() {
+                    pool.update_servers();
+                }
+            }
+        });
+
+        let async_pool = AsyncPool {
+            pool,
+            sockets,
+            update_task,
+        };
+        return Ok(async_pool);
+    }
+
+    // waits until any server has events, see Pool::poll_events
+    pub async fn recv_events(&self) -> Vec<Event> {
+        loop {
+            for socket in &self.sockets {
+                clear_readiness(socket);
+            }
+            let events: Vec<Event> = self.with(|pool| pool.poll_events().collect());
+            if events.len() > 0 {
+                return events;
+            }
+            readable(&self.sockets).await;
+        }
+    }
+
+    pub async fn send(&self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> Result<TachyonSendResult, TachyonError> {
+        return self.with(|pool| pool.try_send_to_target(channel, target, data, length as i32));
+    }
+
+    pub fn with<R, F>(&self, f: F) -> R where F: FnOnce(&mut Pool) -> R {
+        let mut pool = match self.pool.lock() {
+            Ok(pool) => pool,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        return f(&mut pool);
+    }
+}
+
+impl Drop for AsyncPool {
+    fn drop(&mut self) {
+        self.update_task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::tachyon::{network_address::NetworkAddress, tachyon_test::TachyonTestClient, TachyonConfig};
+
+    use super::*;
+
+    fn runtime() -> tokio::runtime::Runtime {
+        return tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_recv() {
+        runtime().block_on(async {
+            let address = NetworkAddress::test_address();
+            let mut server = Tachyon::create(TachyonConfig::default());
+            server.bind(address);
+            let server = AsyncTachyon::create(server, Duration::from_millis(10)).unwrap();
+
+            let mut client = Tachyon::create(TachyonConfig::default());
+            client.connect(address);
+            let client = AsyncTachyon::create(client, Duration::from_millis(10)).unwrap();
+
+            let mut receive_buffer: Vec<u8> = vec![0; 4096];
+            let mut send_buffer: Vec<u8> = vec![0; 4096];
+            let target = SendTarget { address: NetworkAddress::default(), identity_id: 0 };
+
+            let receive = server.recv(&mut receive_buffer);
+            send_buffer[0] = 3;
+            client.send(1, target, &mut send_buffer, 2000).await.unwrap();
+            let result = receive.await.unwrap();
+            assert_eq!(2000, result.length);
+            assert_eq!(3, receive_buffer[0]);
+
+            // woken by the datagram arriving after the wait started
+            let remote = server.with(|tachyon| tachyon.get_connections(1)[0].address);
+            let wait = tokio::spawn(async move {
+                let mut receive_buffer: Vec<u8> = vec![0; 4096];
+                let result = client.recv(&mut receive_buffer).await.unwrap();
+                return result.length;
+            });
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            let target = SendTarget { address: remote, identity_id: 0 };
+            server.send(2, target, &mut send_buffer, 100).await.unwrap();
+            assert_eq!(100, wait.await.unwrap());
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_pool_recv_events() {
+        runtime().block_on(async {
+            let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024 * 4);
+            pool.create_server(TachyonConfig::default(), NetworkAddress::localhost(8001), 1);
+            pool.create_server(TachyonConfig::default(), NetworkAddress::localhost(8002), 2);
+            let pool = AsyncPool::create(pool, Duration::from_millis(10)).unwrap();
+
+            let mut client = TachyonTestClient::create(NetworkAddress::localhost(8002));
+            client.connect();
+            client.client_send_reliable(1, 64);
+
+            let mut events: Vec<Event> = Vec::new();
+            while events.len() < 2 {
+                events.extend(pool.recv_events().await);
+            }
+            match &events[1] {
+                Event::Message { channel: 1, data, .. } => assert_eq!(64, data.len()),
+                _ => panic!("expected a message"),
+            }
+        });
+    }
+}
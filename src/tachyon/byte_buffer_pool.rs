@@ -1,7 +1,7 @@
 use std::collections::VecDeque;
 
 pub const BYTE_BUFFER_SIZE_DEFAULT: usize = 1240;
-const POOL_SIZE_DEFAULT: usize = 512;
+pub const POOL_SIZE_DEFAULT: usize = 512;
 
 
 pub struct ByteBuffer {
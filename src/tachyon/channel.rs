@@ -1,24 +1,53 @@
 
+use std::time::Instant;
+
 use rustc_hash::{FxHashMap};
 
 use super::{
+    delivery::DeliveryTracker,
     fragmentation::Fragmentation,
     header::{
-        Header, MESSAGE_TYPE_FRAGMENT, MESSAGE_TYPE_NONE, MESSAGE_TYPE_NACK,
-        TACHYON_FRAGMENTED_HEADER_SIZE, TACHYON_HEADER_SIZE, MESSAGE_TYPE_RELIABLE_WITH_NACK, MESSAGE_TYPE_RELIABLE, TACHYON_NACKED_HEADER_SIZE
+        Header, SettingsChannel, MESSAGE_TYPE_FRAGMENT, MESSAGE_TYPE_NONE, MESSAGE_TYPE_NACK, MESSAGE_TYPE_ACK,
+        TACHYON_FRAGMENTED_HEADER_SIZE, TACHYON_HEADER_SIZE, MESSAGE_TYPE_RELIABLE_WITH_NACK, MESSAGE_TYPE_RELIABLE, TACHYON_NACKED_HEADER_SIZE,
+        MESSAGE_TYPE_BATCH
     },
+    byte_buffer_pool::ByteBuffer,
     int_buffer::IntBuffer,
     nack::Nack,
     network_address::NetworkAddress,
-    receiver::Receiver,
-    send_buffer_manager::SendBufferManager,
-    tachyon_socket::TachyonSocket, SEND_ERROR_UNKNOWN, TachyonSendResult
+    receive_result::RECEIVE_ERROR_BUFFER_TOO_SMALL,
+    receiver::{Receiver, MAX_REPORTED_SKIPPED},
+    send_buffer_manager::{SendBufferManager, SEND_BUFFER_SIZE},
+    sequence::Sequence,
+    stream::{StreamQueue, STREAM_MAX_FRAGMENTS},
+    tachyon_socket::TachyonSocket,
+    unreliable_sequence::UnreliableSequence, SEND_ERROR_UNKNOWN, TachyonSendResult
 };
+#[cfg(feature = "compression")]
+use super::{compression::Compression, MAX_MESSAGE_SIZE_DEFAULT};
 
 pub static mut NONE_SEND_DATA: &'static mut [u8] = &mut [0; TACHYON_HEADER_SIZE];
 const NACK_REDUNDANCY_DEFAULT: u32 = 1;
 pub const RECEIVE_WINDOW_SIZE_DEFAULT: u32 = 512;
 
+// codecs are only available with the compression feature
+pub const COMPRESSION_NONE: u32 = 0;
+pub const COMPRESSION_LZ4: u32 = 1;
+pub const COMPRESSION_ZSTD: u32 = 2;
+
+// an unchanged ack is resent at this interval in case the last one was dropped
+const ACK_RESEND_INTERVAL: u128 = 250;
+// length prefix of each message in a batch
+pub const BATCH_LENGTH_SIZE: usize = 2;
+
+// the newest message on a reliable latest channel is resent at this interval until acked
+const LATEST_RESEND_INTERVAL: u128 = 100;
+
+// delivery modes, see ChannelConfig.mode
+pub const CHANNEL_MODE_RELIABLE: u32 = 0;
+pub const CHANNEL_MODE_UNRELIABLE_SEQUENCED: u32 = 1;
+pub const CHANNEL_MODE_RELIABLE_LATEST: u32 = 2;
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default, Debug)]
@@ -39,6 +68,22 @@ pub struct ChannelStats {
     pub nones_received: u64,
     pub nones_accepted: u64,
     pub skipped_sequences: u64,
+    pub acks_sent: u64,
+    pub acks_received: u64,
+    pub fragment_groups_expired: u64,
+    pub fragment_groups_evicted: u64,
+    pub dropped_sequences: u64,
+    pub unreliable_sent: u64,
+    pub unreliable_received: u64,
+    pub unreliable_dropped: u64,
+    pub expired: u64,
+    pub batched: u64,
+    // message bytes before and after compression, sent and received
+    pub compress_raw_bytes: u64,
+    pub compress_wire_bytes: u64,
+    pub decompress_wire_bytes: u64,
+    pub decompress_raw_bytes: u64,
+    pub decompress_failed: u64,
 }
 
 impl ChannelStats {
@@ -59,6 +104,21 @@ impl ChannelStats {
         self.nones_received += other.nones_received;
         self.nones_accepted += other.nones_accepted;
         self.skipped_sequences += other.skipped_sequences;
+        self.acks_sent += other.acks_sent;
+        self.acks_received += other.acks_received;
+        self.fragment_groups_expired += other.fragment_groups_expired;
+        self.fragment_groups_evicted += other.fragment_groups_evicted;
+        self.dropped_sequences += other.dropped_sequences;
+        self.unreliable_sent += other.unreliable_sent;
+        self.unreliable_received += other.unreliable_received;
+        self.unreliable_dropped += other.unreliable_dropped;
+        self.expired += other.expired;
+        self.batched += other.batched;
+        self.compress_raw_bytes += other.compress_raw_bytes;
+        self.compress_wire_bytes += other.compress_wire_bytes;
+        self.decompress_wire_bytes += other.decompress_wire_bytes;
+        self.decompress_raw_bytes += other.decompress_raw_bytes;
+        self.decompress_failed += other.decompress_failed;
     }
 }
 
@@ -69,7 +129,10 @@ impl std::fmt::Display for ChannelStats {
             "sent:{} received:{},kb_sent:{} kb_received:{}
 fragments_sent:{} fragments_received:{} fragments_assembled:{},
 published: {} published_consumed:{} nacks_sent:{} nacks_received:{} resent:{}
-nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}\n\n",
+nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}
+acks_sent:{} acks_received:{} fragment_groups_expired:{} fragment_groups_evicted:{} dropped_sequences:{}
+unreliable_sent:{} unreliable_received:{} unreliable_dropped:{} expired:{} batched:{}
+compress_raw_bytes:{} compress_wire_bytes:{} decompress_wire_bytes:{} decompress_raw_bytes:{} decompress_failed:{}\n\n",
             self.sent,
             self.received,
             self.bytes_sent / 1024,
@@ -85,7 +148,22 @@ nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}\n\n",
             self.nones_sent,
             self.nones_received,
             self.nones_accepted,
-            self.skipped_sequences
+            self.skipped_sequences,
+            self.acks_sent,
+            self.acks_received,
+            self.fragment_groups_expired,
+            self.fragment_groups_evicted,
+            self.dropped_sequences,
+            self.unreliable_sent,
+            self.unreliable_received,
+            self.unreliable_dropped,
+            self.expired,
+            self.batched,
+            self.compress_raw_bytes,
+            self.compress_wire_bytes,
+            self.decompress_wire_bytes,
+            self.decompress_raw_bytes,
+            self.decompress_failed
         )
     }
 }
@@ -96,7 +174,25 @@ nones_sent:{} nones_received:{} nones_accepted:{} skipped_sequences:{}\n\n",
 pub struct ChannelConfig {
     pub receive_window_size: u32,
     pub nack_redundancy: u32,
-    pub ordered: u32
+    pub ordered: u32,
+    // track sent messages and report delivered/expired events
+    pub notify_delivery: u32,
+    // queue messages and send fragments as acks open up the window, for large messages
+    pub stream: u32,
+    // CHANNEL_MODE_*, 0 is fully reliable
+    pub mode: u32,
+    // queued traffic from higher priority channels is sent first
+    pub priority: u32,
+    // share of the send budget relative to other channels on the connection, 0 is the same as 1
+    pub weight: u32,
+    // milliseconds a reliable message is resent for before it expires undelivered, 0 for no limit.
+    // Implies notify_delivery.
+    pub ttl: u32,
+    // pack small reliable messages into one datagram sent on update, not used on stream, rate limited, sequenced
+    // or latest channels
+    pub batch: u32,
+    // COMPRESSION_NONE, COMPRESSION_LZ4 or COMPRESSION_ZSTD, applied to reliable messages
+    pub compression: u32
 }
 
 impl ChannelConfig {
@@ -105,7 +201,15 @@ impl ChannelConfig {
         let config = ChannelConfig {
             ordered: 1,
             receive_window_size: RECEIVE_WINDOW_SIZE_DEFAULT,
-            nack_redundancy: NACK_REDUNDANCY_DEFAULT
+            nack_redundancy: NACK_REDUNDANCY_DEFAULT,
+            notify_delivery: 0,
+            stream: 0,
+            mode: CHANNEL_MODE_RELIABLE,
+            priority: 0,
+            weight: 1,
+            ttl: 0,
+            batch: 0,
+            compression: COMPRESSION_NONE
         };
         return config;
     }
@@ -114,7 +218,15 @@ impl ChannelConfig {
         let config = ChannelConfig {
             ordered: 0,
             receive_window_size: RECEIVE_WINDOW_SIZE_DEFAULT,
-            nack_redundancy: NACK_REDUNDANCY_DEFAULT
+            nack_redundancy: NACK_REDUNDANCY_DEFAULT,
+            notify_delivery: 0,
+            stream: 0,
+            mode: CHANNEL_MODE_RELIABLE,
+            priority: 0,
+            weight: 1,
+            ttl: 0,
+            batch: 0,
+            compression: COMPRESSION_NONE
         };
         return config;
     }
@@ -122,6 +234,75 @@ impl ChannelConfig {
     pub fn is_ordered(&self) -> bool {
         return self.ordered == 1;
     }
+
+    pub fn is_notify_delivery(&self) -> bool {
+        return self.notify_delivery == 1 || self.ttl > 0;
+    }
+
+    pub fn is_stream(&self) -> bool {
+        return self.stream == 1 && self.get_mode() == CHANNEL_MODE_RELIABLE;
+    }
+
+    pub fn is_batch(&self) -> bool {
+        return self.batch == 1;
+    }
+
+    // unknown values are no compression, as is everything without the compression feature
+    pub fn get_compression(&self) -> u32 {
+        if cfg!(feature = "compression") && (self.compression == COMPRESSION_LZ4 || self.compression == COMPRESSION_ZSTD) {
+            return self.compression;
+        }
+        return COMPRESSION_NONE;
+    }
+
+    // unknown modes are treated as reliable
+    pub fn get_mode(&self) -> u32 {
+        if self.mode == CHANNEL_MODE_UNRELIABLE_SEQUENCED || self.mode == CHANNEL_MODE_RELIABLE_LATEST {
+            return self.mode;
+        }
+        return CHANNEL_MODE_RELIABLE;
+    }
+
+    pub fn to_settings(&self, channel_id: u8) -> SettingsChannel {
+        let mut settings = SettingsChannel::default();
+        settings.id = channel_id;
+        settings.mode = self.get_mode() as u8;
+        settings.compression = self.get_compression() as u8;
+        if self.is_ordered() && settings.mode as u32 == CHANNEL_MODE_RELIABLE {
+            settings.ordered = 1;
+        }
+        return settings;
+    }
+
+    // config for a channel only the other side has, with it's delivery and our defaults for the rest
+    pub fn from_settings(settings: SettingsChannel) -> Self {
+        let mut config = ChannelConfig::default_unordered();
+        config.ordered = settings.ordered as u32;
+        config.mode = settings.mode as u32;
+        config.compression = settings.compression as u32;
+        return config;
+    }
+
+    // unacked fragments can't be more then half the receive window or the send buffers
+    pub fn get_window(&self) -> usize {
+        return std::cmp::min(self.receive_window_size as usize / 2, SEND_BUFFER_SIZE as usize / 2);
+    }
+}
+
+// A channel configured on both sides of a connection with different delivery, found in the settings exchange.
+// Each side keeps it's own configuration.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct ChannelMismatch {
+    pub address: NetworkAddress,
+    pub channel: u8,
+    pub local_ordered: u8,
+    pub local_mode: u8,
+    pub remote_ordered: u8,
+    pub remote_mode: u8,
+    pub local_compression: u8,
+    pub remote_compression: u8,
 }
 
 pub struct Channel {
@@ -135,24 +316,108 @@ pub struct Channel {
     nacked_sequences: Vec<u16>,
     nacked_sequence_map: FxHashMap<u16, NetworkAddress>,
     pub resend_rewrite_buffer: Vec<u8>,
-    pub nack_redundancy: u32
+    pub nack_redundancy: u32,
+    ack_send_data: Vec<u8>,
+    none_send_data: Vec<u8>,
+    ack_sent_sequence: u16,
+    ack_sent_at: Instant,
+    // smoothed round trip time from acks
+    pub rtt_micros: u64,
+    pub notify_delivery: bool,
+    pub delivery: DeliveryTracker,
+    pub stream: Option<StreamQueue>,
+    pub window: usize,
+    pub mode: u32,
+    latest_sent_at: Instant,
+    // configured differently on the other side, reliable sends fail with SEND_ERROR_CHANNEL
+    pub mismatched: bool,
+    // unreliable sequenced sends have their own sequence and are never buffered
+    sequenced_sequence: u16,
+    sequenced_send_data: Vec<u8>,
+    pub unreliable: UnreliableSequence,
+    pub priority: u32,
+    pub weight: u32,
+    // deficit round robin credit, see scheduler
+    pub deficit: i64,
+    expired_sequences: Vec<u16>,
+    pub batching: bool,
+    // messages waiting for the next flush, length prefixed
+    batch_buffer: Vec<u8>,
+    batch_len: usize,
+    // the batch is sent with the ttl of the messages in it
+    batch_ttl: u32,
+    // received batch being read out and the offset of the next message in it
+    batch_reading: Option<ByteBuffer>,
+    batch_offset: usize,
+    #[cfg(feature = "compression")]
+    pub compression: Compression,
+    #[cfg(feature = "compression")]
+    compress_buffer: Vec<u8>,
+    // decompressed message waiting for a large enough receive buffer
+    #[cfg(feature = "compression")]
+    decompress_buffer: Vec<u8>,
+    #[cfg(feature = "compression")]
+    decompressed_len: usize
 }
 
 impl Channel {
-    pub fn create(id: u8, address: NetworkAddress, config: ChannelConfig) -> Self {
-        let channel = Channel {
+    // buffers are sized to max_datagram_size
+    pub fn create(id: u8, address: NetworkAddress, config: ChannelConfig, max_datagram_size: usize) -> Self {
+        let mut channel = Channel {
             id,
             address,
-            frag: Fragmentation::default(),
-            send_buffers: SendBufferManager::default(),
-            receiver: Receiver::create(config.is_ordered(), config.receive_window_size),
+            frag: Fragmentation::create(Fragmentation::fragment_size_for(max_datagram_size)),
+            send_buffers: SendBufferManager::create(max_datagram_size),
+            receiver: Receiver::create(config.is_ordered(), config.get_mode(), config.receive_window_size, max_datagram_size),
             stats: ChannelStats::default(),
             nack_send_data: vec![0; 512],
             nacked_sequences: Vec::new(),
             nacked_sequence_map: FxHashMap::default(),
-            resend_rewrite_buffer: vec![0;2048],
-            nack_redundancy: config.nack_redundancy
+            resend_rewrite_buffer: vec![0;max_datagram_size],
+            mismatched: false,
+            sequenced_sequence: 0,
+            sequenced_send_data: vec![0;max_datagram_size],
+            nack_redundancy: config.nack_redundancy,
+            ack_send_data: vec![0; TACHYON_HEADER_SIZE + 1 + MAX_REPORTED_SKIPPED * 2],
+            none_send_data: vec![0; TACHYON_HEADER_SIZE],
+            ack_sent_sequence: 0,
+            ack_sent_at: Instant::now(),
+            rtt_micros: 0,
+            notify_delivery: config.is_notify_delivery(),
+            delivery: DeliveryTracker::default(),
+            stream: None,
+            window: config.get_window(),
+            mode: config.get_mode(),
+            latest_sent_at: Instant::now(),
+            unreliable: UnreliableSequence::default(),
+            priority: config.priority,
+            weight: config.weight,
+            deficit: 0,
+            expired_sequences: Vec::new(),
+            batching: config.is_batch(),
+            batch_buffer: Vec::new(),
+            batch_len: 0,
+            batch_ttl: 0,
+            batch_reading: None,
+            batch_offset: 0,
+            #[cfg(feature = "compression")]
+            compression: Compression::create(config.get_compression(), MAX_MESSAGE_SIZE_DEFAULT),
+            #[cfg(feature = "compression")]
+            compress_buffer: Vec::new(),
+            #[cfg(feature = "compression")]
+            decompress_buffer: Vec::new(),
+            #[cfg(feature = "compression")]
+            decompressed_len: 0
         };
+        if config.is_stream() {
+            channel.stream = Some(StreamQueue::default());
+        }
+        channel.send_buffers.ttl = config.ttl;
+        // a rate limited sender streams reliable channels, so it can send up to the stream limit
+        channel.frag.max_fragments = channel.window;
+        if channel.mode == CHANNEL_MODE_RELIABLE {
+            channel.frag.max_fragments = channel.window.max(STREAM_MAX_FRAGMENTS);
+        }
         return channel;
     }
 
@@ -168,26 +433,118 @@ impl Channel {
         return self.receiver.is_ordered;
     }
 
+    // sequenced and latest messages are superseded by newer ones, so they can't be split into fragments
+    pub fn can_fragment(&self) -> bool {
+        return self.mode == CHANNEL_MODE_RELIABLE;
+    }
+
     pub fn update_stats(&mut self) {
         self.stats.skipped_sequences = self.receiver.skipped_sequences;
+        self.stats.dropped_sequences = self.receiver.dropped_sequences;
+        self.stats.unreliable_dropped = self.unreliable.dropped;
+        self.stats.fragment_groups_expired = self.frag.groups_expired;
+        self.stats.fragment_groups_evicted = self.frag.groups_evicted;
     }
 
-    pub fn receive_published(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress) {
+    // returns message length, address, error
+    pub fn receive_published(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, u32) {
+        #[cfg(feature = "compression")]
+        if self.compression.is_enabled() {
+            return self.receive_decompressed(receive_buffer);
+        }
+        return self.receive_published_raw(receive_buffer);
+    }
+
+    fn receive_published_raw(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, u32) {
         for _ in 0..1000 {
             let res = self.receive_published_internal(receive_buffer);
             if res.0 > 0 {
-                return (res.0, res.1);
+                return (res.0, res.1, res.3);
             }
             if !res.2 {
                 break;
             }
         }
 
-        return (0,self.address);
+        return (0,self.address, 0);
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn is_compressed(&self) -> bool {
+        return self.compression.is_enabled();
+    }
+
+    #[cfg(not(feature = "compression"))]
+    pub fn is_compressed(&self) -> bool {
+        return false;
+    }
+
+    // Messages are read into decompress_buffer and decompressed there, so one that's too large for receive_buffer
+    // can be left for the next call like uncompressed messages are.
+    #[cfg(feature = "compression")]
+    fn receive_decompressed(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, u32) {
+        if self.decompressed_len == 0 {
+            let mut wire_buffer = std::mem::take(&mut self.compress_buffer);
+            let mut res = self.receive_published_raw(&mut wire_buffer);
+            if res.2 == RECEIVE_ERROR_BUFFER_TOO_SMALL {
+                wire_buffer.resize(res.0 as usize, 0);
+                res = self.receive_published_raw(&mut wire_buffer);
+            }
+
+            if res.0 == 0 || res.2 != 0 {
+                self.compress_buffer = wire_buffer;
+                return res;
+            }
+
+            let wire_len = res.0 as usize;
+            match self.compression.decompress(&wire_buffer[0..wire_len], &mut self.decompress_buffer) {
+                Some(length) => {
+                    self.decompressed_len = length;
+                    self.stats.decompress_wire_bytes += wire_len as u64;
+                    self.stats.decompress_raw_bytes += length as u64;
+                }
+                None => {
+                    self.stats.decompress_failed += 1;
+                }
+            }
+            self.compress_buffer = wire_buffer;
+            if self.decompressed_len == 0 {
+                return (0, self.address, 0);
+            }
+        }
+
+        let length = self.decompressed_len;
+        if length > receive_buffer.len() {
+            return (length as u32, self.address, RECEIVE_ERROR_BUFFER_TOO_SMALL);
+        }
+        receive_buffer[0..length].copy_from_slice(&self.decompress_buffer[0..length]);
+        self.decompressed_len = 0;
+        return (length as u32, self.address, 0);
+    }
+
+    // Compresses into the channel's buffer and hands it out to send from, give it back with return_compress_buffer.
+    // Returns the buffer and compressed length.
+    #[cfg(feature = "compression")]
+    pub fn compress(&mut self, data: &[u8]) -> (Vec<u8>, usize) {
+        let mut compressed = std::mem::take(&mut self.compress_buffer);
+        let length = self.compression.compress(data, &mut compressed);
+        self.stats.compress_raw_bytes += data.len() as u64;
+        self.stats.compress_wire_bytes += length as u64;
+        return (compressed, length);
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn return_compress_buffer(&mut self, buffer: Vec<u8>) {
+        self.compress_buffer = buffer;
     }
 
-    // returns message length, address, should retry (queue not empty)
-    fn receive_published_internal(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, bool) {
+    // returns message length, address, should retry (queue not empty), error.
+    // If receive_buffer is too small the length is the size needed and the message is left at the front of the queue.
+    fn receive_published_internal(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, bool, u32) {
+        if self.batch_reading.is_some() {
+            return self.receive_batched(receive_buffer);
+        }
+
         match self.receiver.take_published() {
             Some(byte_buffer) => {
                 let buffer_len = byte_buffer.length;
@@ -197,23 +554,38 @@ impl Channel {
 
                 if message_type == MESSAGE_TYPE_NONE {
                     self.receiver.return_buffer(byte_buffer);
-                    return (0, self.address, true);
+                    return (0, self.address, true, 0);
+                }
+
+                if message_type == MESSAGE_TYPE_BATCH {
+                    self.batch_reading = Some(byte_buffer);
+                    self.batch_offset = TACHYON_HEADER_SIZE;
+                    return self.receive_batched(receive_buffer);
                 }
 
                 if message_type == MESSAGE_TYPE_FRAGMENT {
                     let header = Header::read_fragmented(&byte_buffer.get());
+                    if let Some(assembled_len) = self.frag.get_assembled_length(header.fragment_group) {
+                        if assembled_len > receive_buffer.len() {
+                            self.receiver.return_published(byte_buffer);
+                            return (assembled_len as u32, self.address, false, RECEIVE_ERROR_BUFFER_TOO_SMALL);
+                        }
+                    }
+
                     match self.frag.assemble(header) {
-                        Ok(res) => {
-                            let assembled_len = res.len();
-                            receive_buffer[0..assembled_len].copy_from_slice(&res[..]);
+                        Ok(assembled) => {
+                            let assembled_len = assembled.length;
+                            receive_buffer[0..assembled_len].copy_from_slice(&assembled.get()[0..assembled_len]);
+                            self.frag.return_buffer(assembled);
+                            self.receiver.return_buffer(byte_buffer);
                             self.stats.received += 1;
                             self.stats.fragments_assembled += header.fragment_count as u64;
                             self.stats.published_consumed += 1;
-                            return (assembled_len as u32, self.address, true);
+                            return (assembled_len as u32, self.address, true, 0);
                         }
                         Err(_) => {
                             self.receiver.return_buffer(byte_buffer);
-                            return (0, self.address, true);
+                            return (0, self.address, true, 0);
                         }
                     }
                 }
@@ -225,22 +597,75 @@ impl Channel {
                     header_size = TACHYON_HEADER_SIZE;
                 } else {
                     // should not be possible
-                    return (0, self.address, true);
+                    return (0, self.address, true, 0);
+                }
+
+                let body_len = buffer_len - header_size;
+                if body_len > receive_buffer.len() {
+                    self.receiver.return_published(byte_buffer);
+                    return (body_len as u32, self.address, false, RECEIVE_ERROR_BUFFER_TOO_SMALL);
                 }
 
                 receive_buffer[0..buffer_len - header_size].copy_from_slice(&byte_buffer.get()[header_size..buffer_len]);
                 self.receiver.return_buffer(byte_buffer);
 
                 self.stats.published_consumed += 1;
-                return ((buffer_len - header_size) as u32, self.address, true);
+                return (body_len as u32, self.address, true, 0);
             }
             None => {
-                return (0, self.address, false);
+                return (0, self.address, false, 0);
             }
         }
         
     }
 
+    // next message out of the batch being read, the batch is released after the last one
+    fn receive_batched(&mut self, receive_buffer: &mut [u8]) -> (u32, NetworkAddress, bool, u32) {
+        let byte_buffer = match &self.batch_reading {
+            Some(byte_buffer) => byte_buffer,
+            None => {
+                return (0, self.address, true, 0);
+            }
+        };
+
+        let buffer_len = byte_buffer.length;
+        if self.batch_offset + BATCH_LENGTH_SIZE > buffer_len {
+            if let Some(byte_buffer) = self.batch_reading.take() {
+                self.receiver.return_buffer(byte_buffer);
+            }
+            return (0, self.address, true, 0);
+        }
+
+        let mut reader = IntBuffer { index: self.batch_offset };
+        let message_len = reader.read_u16(&byte_buffer.get()) as usize;
+        let start = self.batch_offset + BATCH_LENGTH_SIZE;
+        if start + message_len > buffer_len {
+            // malformed, drop the rest
+            if let Some(byte_buffer) = self.batch_reading.take() {
+                self.receiver.return_buffer(byte_buffer);
+            }
+            return (0, self.address, true, 0);
+        }
+
+        if message_len > receive_buffer.len() {
+            return (message_len as u32, self.address, false, RECEIVE_ERROR_BUFFER_TOO_SMALL);
+        }
+
+        receive_buffer[0..message_len].copy_from_slice(&byte_buffer.get()[start..start + message_len]);
+        self.batch_offset = start + message_len;
+        self.stats.published_consumed += 1;
+        return (message_len as u32, self.address, true, 0);
+    }
+
+    // returns false for duplicates and messages too far behind
+    pub fn process_unreliable_message(&mut self, sequence: u16) -> bool {
+        if self.unreliable.receive(sequence) {
+            self.stats.unreliable_received += 1;
+            return true;
+        }
+        return false;
+    }
+
     pub fn process_none_message(&mut self, sequence: u16, receive_buffer: &mut [u8], received_len: usize) {
         self.stats.nones_received += 1;
         if self.receiver.receive_packet(sequence, receive_buffer, received_len)
@@ -249,15 +674,80 @@ impl Channel {
         }
     }
 
+    // Resends of fragments already received never reach the group.  A fragment the group rejects is received as a none
+    // so it isn't nacked forever, the group then expires and is reported lost.
     pub fn process_fragment_message(&mut self, sequence: u16, receive_buffer: &mut [u8], received_len: usize) {
+        if !Sequence::is_greater_then(sequence, self.receiver.current_sequence) || self.receiver.is_received(sequence) {
+            return;
+        }
+
         let received_frag_res = self.frag.receive_fragment(receive_buffer, received_len);
         if received_frag_res.0 {
             if self.receiver.receive_packet(sequence,receive_buffer,TACHYON_FRAGMENTED_HEADER_SIZE) {
                 self.stats.fragments_received += 1;
             }
+        } else {
+            let none = [MESSAGE_TYPE_NONE];
+            self.receiver.receive_packet(sequence, &none, none.len());
         }
     }
 
+    // Ack message, the skipped sequences are marked lost before the ack covers them
+    pub fn process_ack_message(&mut self, sequence: u16, receive_buffer: &[u8], received_len: usize) {
+        if received_len > TACHYON_HEADER_SIZE {
+            let mut reader = IntBuffer { index: TACHYON_HEADER_SIZE };
+            let count = reader.read_u8(receive_buffer) as usize;
+            if received_len >= TACHYON_HEADER_SIZE + 1 + count * 2 {
+                for _ in 0..count {
+                    let skipped = reader.read_u16(receive_buffer);
+                    if Sequence::is_equal_to_or_less_than(skipped, sequence) && !Sequence::is_greater_then(skipped, self.send_buffers.current_sequence) {
+                        self.send_buffers.set_lost(skipped);
+                    }
+                }
+            }
+        }
+        self.process_ack(sequence);
+    }
+
+    // cumulative ack from the other side, sent on it's own or in the header of a nack message
+    pub fn process_ack(&mut self, sequence: u16) {
+        if self.send_buffers.is_buffered(sequence) {
+            if let Some(send_buffer) = self.send_buffers.get_send_buffer(sequence) {
+                // resent buffers are skipped, the ack could be for any of it's sends
+                if !send_buffer.resent {
+                    let sample = send_buffer.created_at.elapsed().as_micros() as u64;
+                    if self.rtt_micros == 0 {
+                        self.rtt_micros = sample;
+                    } else {
+                        self.rtt_micros = (self.rtt_micros * 7 + sample) / 8;
+                    }
+                }
+            }
+        }
+
+        if self.send_buffers.ack(sequence) > 0 {
+            self.stats.acks_received += 1;
+            // messages sent with a ttl are tracked even when the channel doesn't notify
+            if self.delivery.pending_count() > 0 {
+                self.delivery.on_ack(&self.send_buffers, self.address, self.id);
+            }
+        }
+    }
+
+    pub fn track_delivery(&mut self, sequence: u16, fragment_count: u16) {
+        if self.notify_delivery {
+            self.delivery.track(sequence, fragment_count);
+        }
+    }
+
+    // never for sequenced channels, they aren't acked
+    pub fn is_delivered(&self, sequence: u16) -> bool {
+        if self.mode == CHANNEL_MODE_UNRELIABLE_SEQUENCED {
+            return false;
+        }
+        return self.send_buffers.is_acked(sequence);
+    }
+
     // separate nack message, varint encoded
     pub fn process_nack_message(&mut self, address: NetworkAddress, receive_buffer: &mut [u8]) {
         self.nacked_sequences.clear();
@@ -273,14 +763,53 @@ impl Channel {
     }
 
 
+    // Sent once and never resent, so there is no send buffer, nack or ack.  The receiver only needs the sequence to
+    // drop anything older then what it already delivered.
+    pub fn send_sequenced(&mut self, address: NetworkAddress, data: &[u8], body_len: usize, socket: &TachyonSocket) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+        let length = body_len + TACHYON_HEADER_SIZE;
+        if self.sequenced_send_data.len() < length {
+            self.sequenced_send_data.resize(length, 0);
+        }
+
+        self.sequenced_sequence = Sequence::next_sequence(self.sequenced_sequence);
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_RELIABLE;
+        header.channel = self.id;
+        header.sequence = self.sequenced_sequence;
+        header.write(&mut self.sequenced_send_data);
+        self.sequenced_send_data[TACHYON_HEADER_SIZE..length].copy_from_slice(&data[0..body_len]);
+
+        let sent_len = socket.send_to(address, &self.sequenced_send_data, length);
+        result.sent_len = sent_len as u32;
+        result.header = header;
+
+        self.stats.bytes_sent += sent_len as u64;
+        self.stats.sent += 1;
+        return result;
+    }
+
     pub fn send_reliable(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize, socket: &TachyonSocket) -> TachyonSendResult {
+        let result = self.send_reliable_buffer(address, data, body_len, socket);
+        if result.error == 0 {
+            self.track_delivery(result.header.sequence, 1);
+        }
+        return result;
+    }
+
+    // batches don't carry nacks, so the receiver can tell them apart by message type
+    fn send_reliable_buffer(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize, socket: &TachyonSocket) -> TachyonSendResult {
+        return self.send_reliable_buffer_typed(address, data, body_len, socket, MESSAGE_TYPE_RELIABLE);
+    }
+
+    fn send_reliable_buffer_typed(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize, socket: &TachyonSocket, message_type: u8) -> TachyonSendResult {
         let mut result = TachyonSendResult::default();
 
         // Optionally include nacks in outgoing messages, up to nack_redundancy times for each nack
         let mut nack_option: Option<Nack> = None;
         let mut header_len = TACHYON_HEADER_SIZE;
        
-        if self.nack_redundancy > 0 {
+        if self.nack_redundancy > 0 && message_type == MESSAGE_TYPE_RELIABLE {
             if let Some(mut nack) = self.receiver.nack_queue.pop_front() {
                 if nack.sent_count < self.nack_redundancy  {
                     nack.sent_count += 1;
@@ -309,7 +838,7 @@ impl Channel {
 
                     self.stats.nacks_sent += nack.nacked_count as u64;
                 } else {
-                    header.message_type = MESSAGE_TYPE_RELIABLE;
+                    header.message_type = message_type;
                 }
                 
                 header.write(&mut send_buffer.byte_buffer.get_mut());
@@ -317,6 +846,7 @@ impl Channel {
                 let sent_len = socket.send_to(address, &send_buffer.byte_buffer.get(), send_buffer_len);
                 result.sent_len = sent_len as u32;
                 result.header = header;
+                self.latest_sent_at = Instant::now();
 
                 self.stats.bytes_sent += sent_len as u64;
                 self.stats.sent += 1;
@@ -330,16 +860,218 @@ impl Channel {
         }
     }
 
+    pub fn is_stream(&self) -> bool {
+        return self.stream.is_some();
+    }
+
+    // only plain reliable channels, a batch on a latest channel would be published as one message
+    pub fn is_batching(&self) -> bool {
+        return self.batching && !self.is_stream() && self.mode == CHANNEL_MODE_RELIABLE;
+    }
+
+    // largest batch body, anything bigger would be fragmented
+    fn batch_capacity(&self) -> usize {
+        return self.frag.fragment_size - 1;
+    }
+
+    pub fn can_batch(&self, body_len: usize) -> bool {
+        return body_len + BATCH_LENGTH_SIZE <= self.batch_capacity();
+    }
+
+    // Add a message to the batch, flushing first if it doesn't fit.  The header has the sequence the batch will be
+    // sent with, that holds as long as the batch is flushed before anything else is sent on the channel.
+    pub fn send_batched(&mut self, data: &[u8], body_len: usize, socket: &TachyonSocket) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+
+        // a message with a different ttl starts a new batch
+        if self.batch_len + BATCH_LENGTH_SIZE + body_len > self.batch_capacity() || self.batch_ttl != self.send_buffers.ttl {
+            self.flush_batch(socket);
+        }
+        self.batch_ttl = self.send_buffers.ttl;
+
+        if self.batch_buffer.len() < self.batch_capacity() {
+            self.batch_buffer.resize(self.batch_capacity(), 0);
+        }
+
+        let mut writer = IntBuffer { index: self.batch_len };
+        writer.write_u16(body_len as u16, &mut self.batch_buffer);
+        self.batch_buffer[writer.index..writer.index + body_len].copy_from_slice(&data[0..body_len]);
+        self.batch_len = writer.index + body_len;
+        self.stats.batched += 1;
+
+        result.header.message_type = MESSAGE_TYPE_BATCH;
+        result.header.channel = self.id;
+        result.header.sequence = Sequence::next_sequence(self.send_buffers.current_sequence);
+        self.track_delivery(result.header.sequence, 1);
+        return result;
+    }
+
+    pub fn flush_batch(&mut self, socket: &TachyonSocket) {
+        if self.batch_len == 0 {
+            return;
+        }
+
+        let ttl = self.send_buffers.ttl;
+        self.send_buffers.ttl = self.batch_ttl;
+        let mut batch_buffer = std::mem::take(&mut self.batch_buffer);
+        self.send_reliable_buffer_typed(self.address, &mut batch_buffer, self.batch_len, socket, MESSAGE_TYPE_BATCH);
+        self.batch_buffer = batch_buffer;
+        self.batch_len = 0;
+        self.send_buffers.ttl = ttl;
+    }
+
+    // most fragments a single message can be split into
+    pub fn max_fragments(&self) -> usize {
+        if self.is_stream() {
+            return STREAM_MAX_FRAGMENTS;
+        }
+        return self.window;
+    }
+
+    pub fn has_queued(&self) -> bool {
+        match &self.stream {
+            Some(stream) => {
+                return !stream.is_empty();
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    // everything on a stream channel goes through the queue, the scheduler sends it as the window and send rate allow
+    pub fn send_stream(&mut self, data: &[u8], body_len: usize) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+
+        let fragmented = self.frag.should_fragment(body_len);
+        let mut fragment_count: u16 = 1;
+        let mut group: u16 = 0;
+        if fragmented {
+            fragment_count = self.frag.get_fragment_count(body_len) as u16;
+            group = self.frag.get_next_group();
+        }
+
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                result.error = SEND_ERROR_UNKNOWN;
+                return result;
+            }
+        };
+
+        let message = stream.queue(&data[0..body_len], self.frag.fragment_size, fragment_count, group, self.send_buffers.current_sequence, self.send_buffers.ttl);
+        result.header.channel = self.id;
+        result.header.sequence = message.start_sequence;
+        if fragmented {
+            result.header.message_type = MESSAGE_TYPE_FRAGMENT;
+            result.header.fragment_start_sequence = message.start_sequence;
+            result.header.fragment_count = fragment_count;
+        } else {
+            result.header.message_type = MESSAGE_TYPE_RELIABLE;
+        }
+
+        self.track_delivery(result.header.sequence, fragment_count);
+        return result;
+    }
+
+    // Sends queued stream messages while unacked sequences are under the window and less then max_bytes have been sent,
+    // returns bytes sent.  Can go over max_bytes by one datagram.
+    pub fn pump_stream(&mut self, socket: &TachyonSocket, max_bytes: usize) -> usize {
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => {
+                return 0;
+            }
+        };
+
+        // queued messages keep the ttl they were sent with
+        let channel_ttl = self.send_buffers.ttl;
+        let mut bytes_sent = 0;
+        while let Some(message) = stream.messages.front_mut() {
+            let unacked = Sequence::distance(self.send_buffers.acked_sequence, self.send_buffers.current_sequence) as usize;
+            if unacked >= self.window || bytes_sent >= max_bytes {
+                break;
+            }
+
+            self.send_buffers.ttl = message.ttl;
+            let message_len = message.data.len();
+            if !message.fragmented {
+                // stays queued if it didn't get a send buffer, it's sequence is reserved
+                let result = self.send_reliable_buffer(self.address, &mut message.data, message_len, socket);
+                if result.error != 0 {
+                    break;
+                }
+                bytes_sent += result.sent_len as usize;
+                stream.queued_bytes -= message_len;
+                stream.messages.pop_front();
+                continue;
+            }
+
+            let end = std::cmp::min(message.offset + message.fragment_size, message_len);
+            let chunk = &message.data[message.offset..end];
+            match Fragmentation::create_fragment(&mut self.send_buffers, self.id, message.group, message.start_sequence, message.fragment_count, chunk) {
+                Some(sequence) => {
+                    if let Some(fragment) = self.send_buffers.get_send_buffer(sequence) {
+                        let sent = socket.send_to(self.address, &fragment.byte_buffer.get(), fragment.byte_buffer.length);
+                        bytes_sent += sent;
+                        self.stats.bytes_sent += sent as u64;
+                        self.stats.fragments_sent += 1;
+                    }
+                }
+                None => {
+                    break;
+                }
+            }
+
+            message.offset = end;
+            if message.offset >= message_len {
+                stream.queued_bytes -= message_len;
+                stream.messages.pop_front();
+                self.stats.sent += 1;
+            }
+        }
+
+        self.send_buffers.ttl = channel_ttl;
+        self.stream = Some(stream);
+        return bytes_sent;
+    }
+
     pub fn update(&mut self,socket: &TachyonSocket) {
-        self.send_nacks(socket);
+        self.flush_batch(socket);
+
+        // sequenced channels never nack and the sender has no use for acks
+        if self.mode != CHANNEL_MODE_UNRELIABLE_SEQUENCED {
+            // nack headers can't carry skipped sequences, so acks that do still go out
+            if !self.send_nacks(socket) || self.receiver.skipped.len() > 0 {
+                self.send_ack(socket);
+            }
+        }
         self.resend_nacked(socket);
+        self.resend_latest(socket);
+        self.expire_ttl(socket);
 
-        // this takes way too long if there are a lot of frag groups, disabling until I find a better solution
-        //self.frag.expire_groups();
+        if self.delivery.pending_count() > 0 {
+            self.delivery.expire(&self.send_buffers, self.address, self.id);
+        }
+
+        self.frag.expire_groups();
+        self.report_dropped_groups();
 
         self.receiver.publish();
     }
 
+    // Fragments of expired and evicted groups are reported as skipped, so the sender sees the message expired and
+    // not delivered.  Ones not received yet are listed ahead of current.
+    pub fn report_dropped_groups(&mut self) {
+        for (start_sequence, fragment_count) in std::mem::take(&mut self.frag.dropped) {
+            let mut sequence = start_sequence;
+            for _ in 0..fragment_count {
+                self.receiver.set_skipped(sequence);
+                sequence = Sequence::next_sequence(sequence);
+            }
+        }
+    }
+
     fn copy_nacked_to_map(&mut self, address: NetworkAddress) {
         for sequence in &self.nacked_sequences {
             self.nacked_sequence_map.insert(*sequence, address);
@@ -355,9 +1087,17 @@ impl Channel {
             return;
         }
 
+        let current_sequence = self.send_buffers.current_sequence;
         for (sequence, address) in &self.nacked_sequence_map {
             self.stats.nacks_received += 1;
-            match self.send_buffers.get_send_buffer(*sequence) {
+
+            // on latest channels anything older then the newest message is answered with a none
+            let mut send_buffer_option = self.send_buffers.get_send_buffer(*sequence);
+            if self.mode == CHANNEL_MODE_RELIABLE_LATEST && *sequence != current_sequence {
+                send_buffer_option = None;
+            }
+
+            match send_buffer_option {
                 Some(send_buffer) => {
 
                     let mut reader = IntBuffer { index: 0 };
@@ -372,10 +1112,14 @@ impl Channel {
                     } else {
                         socket.send_to(*address, &send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
                     }
+                    send_buffer.resent = true;
                     
                     self.stats.resent += 1;
                 }
                 None => {
+                    if !self.send_buffers.is_acked(*sequence) {
+                        self.send_buffers.set_lost(*sequence);
+                    }
                     Channel::create_none(*sequence, self.id);
                     let _sent_len = socket.send_to(*address,unsafe { &NONE_SEND_DATA },TACHYON_HEADER_SIZE);
                     self.stats.nones_sent += 1;
@@ -385,23 +1129,143 @@ impl Channel {
         self.nacked_sequence_map.clear();
     }
 
-    // Send nacks for sequences we are missing
-    fn send_nacks(&mut self, socket: &TachyonSocket) {
+    // Drop messages past their ttl so they are never resent, and send nones for them right away so the receiver
+    // doesn't wait on a nack round trip to skip them.
+    fn expire_ttl(&mut self, socket: &TachyonSocket) {
+        self.expired_sequences.clear();
+        self.send_buffers.expire(Instant::now(), &mut self.expired_sequences);
+
+        for sequence in &self.expired_sequences {
+            let mut header = Header::default();
+            header.message_type = MESSAGE_TYPE_NONE;
+            header.sequence = *sequence;
+            header.channel = self.id;
+            header.write(&mut self.none_send_data);
+            socket.send_to(self.address, &self.none_send_data, TACHYON_HEADER_SIZE);
+            self.stats.nones_sent += 1;
+            self.stats.expired += 1;
+        }
+    }
+
+    // The newest message on a latest channel has to arrive even if nothing is sent after it to trigger a nack,
+    // so keep resending it until the cumulative ack covers it.
+    fn resend_latest(&mut self, socket: &TachyonSocket) {
+        if self.mode != CHANNEL_MODE_RELIABLE_LATEST {
+            return;
+        }
+
+        let sequence = self.send_buffers.current_sequence;
+        if self.send_buffers.is_acked(sequence) || self.latest_sent_at.elapsed().as_millis() < LATEST_RESEND_INTERVAL {
+            return;
+        }
+
+        if let Some(send_buffer) = self.send_buffers.get_send_buffer(sequence) {
+            let mut reader = IntBuffer { index: 0 };
+            let message_type = reader.read_u8(&send_buffer.byte_buffer.get());
+            if message_type == MESSAGE_TYPE_RELIABLE_WITH_NACK {
+                let send_len = Channel::rewrite_reliable_nack_to_reliable(&mut self.resend_rewrite_buffer,&send_buffer.byte_buffer.get());
+                socket.send_to(self.address, &self.resend_rewrite_buffer, send_len);
+            } else {
+                socket.send_to(self.address, &send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+            }
+            send_buffer.resent = true;
+            self.stats.resent += 1;
+            self.latest_sent_at = Instant::now();
+        }
+    }
+
+    // Send nacks for sequences we are missing. The header sequence carries our current sequence as a cumulative ack.
+    // Returns true if a nack message was sent.
+    fn send_nacks(&mut self, socket: &TachyonSocket) -> bool {
         
         let nack_count = self.receiver.create_nacks();
         if self.receiver.nack_list.len() == 0 {
-            return;
+            return false;
         }
 
         let mut header = Header::default();
         header.message_type = MESSAGE_TYPE_NACK;
         header.channel = self.id;
+        header.sequence = self.get_ack(self.receiver.get_safe_ack());
         header.write(&mut self.nack_send_data);
 
         let position = Nack::write_varint(&self.receiver.nack_list, &mut self.nack_send_data, TACHYON_HEADER_SIZE as u64);
         socket.send_to(self.address, &self.nack_send_data, position as usize);
 
         self.stats.nacks_sent += nack_count as u64;
+        self.on_ack_sent();
+        return true;
+    }
+
+    // Send our current sequence when it has changed, or periodically if it hasn't.
+    fn send_ack(&mut self, socket: &TachyonSocket) {
+        let current_sequence = self.receiver.current_sequence;
+
+        // nothing received yet
+        if current_sequence == 0 && self.receiver.last_sequence == 0 {
+            return;
+        }
+
+        if self.get_ack(current_sequence) == self.ack_sent_sequence && self.ack_sent_at.elapsed().as_millis() < ACK_RESEND_INTERVAL {
+            return;
+        }
+
+        // Followed by the skipped sequences the ack covers, oldest first and up to MAX_REPORTED_SKIPPED per datagram.
+        // Each datagram only acks up to the sequence before the first one in the next, so it's safe on it's own
+        // if the others are lost.
+        let skipped_count = self.receiver.skipped.len();
+        let mut start = 0;
+        loop {
+            let end = std::cmp::min(start + MAX_REPORTED_SKIPPED, skipped_count);
+            let mut header = Header::default();
+            header.message_type = MESSAGE_TYPE_ACK;
+            header.channel = self.id;
+            header.sequence = match self.receiver.skipped.get(end) {
+                Some(next) => self.get_ack(Sequence::previous_sequence(*next)),
+                None => self.get_ack(current_sequence),
+            };
+            header.write(&mut self.ack_send_data);
+
+            let mut writer = IntBuffer { index: TACHYON_HEADER_SIZE };
+            writer.write_u8((end - start) as u8, &mut self.ack_send_data);
+            for sequence in self.receiver.skipped.range(start..end) {
+                writer.write_u16(*sequence, &mut self.ack_send_data);
+            }
+            socket.send_to(self.address, &self.ack_send_data, writer.index);
+
+            start = end;
+            if start >= skipped_count {
+                break;
+            }
+        }
+        self.on_ack_sent();
+    }
+
+    // Sequence acked no further then current, or the start of a fragment group still being received.  The group's
+    // fragments are only delivered as a whole.  Streams send the rest of a group as the first fragments are acked,
+    // so they aren't held and rely on dropped groups being reported as skipped.
+    fn get_ack(&self, sequence: u16) -> u16 {
+        let current_sequence = self.receiver.current_sequence;
+        let mut ack = sequence;
+        if Sequence::is_greater_then(ack, current_sequence) {
+            ack = current_sequence;
+        }
+        if self.is_stream() {
+            return ack;
+        }
+        if let Some(start_sequence) = self.frag.oldest_incomplete_sequence(current_sequence) {
+            let held = Sequence::previous_sequence(start_sequence);
+            if Sequence::is_greater_then(ack, held) {
+                ack = held;
+            }
+        }
+        return ack;
+    }
+
+    fn on_ack_sent(&mut self) {
+        self.ack_sent_sequence = self.get_ack(self.receiver.current_sequence);
+        self.ack_sent_at = Instant::now();
+        self.stats.acks_sent += 1;
     }
 
     pub fn rewrite_reliable_nack_to_reliable(rewrite_buffer: &mut [u8], send_buffer: &[u8]) -> usize {
@@ -422,7 +1286,7 @@ impl Channel {
 #[cfg(test)]
 mod tests {
 
-    use crate::tachyon::{header::{Header, MESSAGE_TYPE_RELIABLE_WITH_NACK,  MESSAGE_TYPE_RELIABLE}, network_address::NetworkAddress, channel::ChannelConfig};
+    use crate::tachyon::{header::{Header, MESSAGE_TYPE_RELIABLE_WITH_NACK,  MESSAGE_TYPE_RELIABLE}, network_address::NetworkAddress, channel::ChannelConfig, sequence::Sequence, MAX_DATAGRAM_SIZE_DEFAULT};
 
     use super::Channel;
 
@@ -430,7 +1294,7 @@ mod tests {
     #[test]
     fn test_rewrite_nack_to_reliable() {
 
-        let mut channel = Channel::create(1, NetworkAddress::default(), ChannelConfig::default_ordered());
+        let mut channel = Channel::create(1, NetworkAddress::default(), ChannelConfig::default_ordered(), MAX_DATAGRAM_SIZE_DEFAULT);
         let mut send_buffer: Vec<u8> = vec![0;1200];
         let mut header = Header::default();
         header.message_type = MESSAGE_TYPE_RELIABLE_WITH_NACK;
@@ -456,5 +1320,59 @@ mod tests {
        
     }
 
+    #[test]
+    fn test_rtt_skips_resent() {
+        let mut channel = Channel::create(1, NetworkAddress::default(), ChannelConfig::default_ordered(), MAX_DATAGRAM_SIZE_DEFAULT);
+        channel.send_buffers.create_send_buffer(32).unwrap().resent = true;
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        channel.process_ack(channel.send_buffers.current_sequence);
+        assert_eq!(0, channel.rtt_micros);
+
+        channel.send_buffers.create_send_buffer(32);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        channel.process_ack(channel.send_buffers.current_sequence);
+        assert!(channel.rtt_micros >= 2000);
+    }
+
+    fn receive_fragments(sender: &mut Channel, receiver: &mut Channel, fragments: &[u16]) {
+        for sequence in fragments {
+            let send_buffer = sender.send_buffers.get_send_buffer(*sequence).unwrap();
+            let length = send_buffer.byte_buffer.length;
+            receiver.process_fragment_message(*sequence, send_buffer.byte_buffer.get_mut(), length);
+        }
+    }
+
+    #[test]
+    fn test_ack_fragment_group() {
+        let mut sender = Channel::create(1, NetworkAddress::default(), ChannelConfig::default_ordered(), MAX_DATAGRAM_SIZE_DEFAULT);
+        let mut receiver = Channel::create(1, NetworkAddress::default(), ChannelConfig::default_ordered(), MAX_DATAGRAM_SIZE_DEFAULT);
+        let data: Vec<u8> = vec![3; 2500];
+
+        // held before the group until it's complete
+        let fragments = sender.frag.create_fragments(&mut sender.send_buffers, 1, &data, data.len());
+        assert_eq!(3, fragments.len());
+        receive_fragments(&mut sender, &mut receiver, &fragments[0..2]);
+        assert_eq!(fragments[1], receiver.receiver.current_sequence);
+        assert_eq!(Sequence::previous_sequence(fragments[0]), receiver.get_ack(receiver.receiver.current_sequence));
+        receive_fragments(&mut sender, &mut receiver, &fragments[2..3]);
+        assert_eq!(fragments[2], receiver.get_ack(receiver.receiver.current_sequence));
+        receiver.receiver.publish();
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        assert_eq!(2500, receiver.receive_published(&mut receive_buffer).0);
+
+        // a dropped group is reported as skipped, fragments that arrive after it included
+        let fragments = sender.frag.create_fragments(&mut sender.send_buffers, 1, &data, data.len());
+        receive_fragments(&mut sender, &mut receiver, &fragments[0..1]);
+        assert!(receiver.frag.evict_oldest());
+        receiver.report_dropped_groups();
+        assert_eq!(fragments, receiver.receiver.skipped.iter().copied().collect::<Vec<u16>>());
+        assert_eq!(fragments[0], receiver.get_ack(receiver.receiver.current_sequence));
+        assert_eq!(Sequence::previous_sequence(fragments[0]), receiver.receiver.get_safe_ack());
+
+        receive_fragments(&mut sender, &mut receiver, &fragments[1..3]);
+        assert_eq!(fragments[2], receiver.get_ack(receiver.receiver.current_sequence));
+        assert_eq!(3, receiver.receiver.skipped.len());
+    }
+
 }
 
@@ -0,0 +1,184 @@
+use super::int_buffer::IntBuffer;
+
+pub use super::channel::{COMPRESSION_NONE, COMPRESSION_LZ4, COMPRESSION_ZSTD};
+
+pub const ZSTD_LEVEL: i32 = 3;
+
+// codec and uncompressed length in front of every message on a compressed channel
+pub const COMPRESSION_HEADER_SIZE: usize = 5;
+
+// codec byte, a message that didn't get smaller is sent as is
+const CODEC_RAW: u8 = 0;
+
+// Compresses whole messages on a channel, before fragmenting and batching, and decompresses them after.
+// zstd contexts are created on first use so channels that never send don't pay for them.
+pub struct Compression {
+    pub codec: u32,
+    pub max_message_size: usize,
+    dictionary: Option<Vec<u8>>,
+    zstd_compressor: Option<zstd::bulk::Compressor<'static>>,
+    zstd_decompressor: Option<zstd::bulk::Decompressor<'static>>,
+}
+
+impl Compression {
+    pub fn create(codec: u32, max_message_size: usize) -> Self {
+        let mut compression = Compression {
+            codec: COMPRESSION_NONE,
+            max_message_size,
+            dictionary: None,
+            zstd_compressor: None,
+            zstd_decompressor: None,
+        };
+        if codec == COMPRESSION_LZ4 || codec == COMPRESSION_ZSTD {
+            compression.codec = codec;
+        }
+        return compression;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        return self.codec != COMPRESSION_NONE;
+    }
+
+    pub fn has_dictionary(&self) -> bool {
+        return self.dictionary.is_some();
+    }
+
+    // only used by zstd, both sides need the same dictionary
+    pub fn set_dictionary(&mut self, dictionary: Option<Vec<u8>>) {
+        self.dictionary = dictionary;
+        self.zstd_compressor = None;
+        self.zstd_decompressor = None;
+    }
+
+    fn compressor(&mut self) -> Option<&mut zstd::bulk::Compressor<'static>> {
+        if self.zstd_compressor.is_none() {
+            let compressor = match &self.dictionary {
+                Some(dictionary) => zstd::bulk::Compressor::with_dictionary(ZSTD_LEVEL, dictionary),
+                None => zstd::bulk::Compressor::new(ZSTD_LEVEL),
+            };
+            self.zstd_compressor = compressor.ok();
+        }
+        return self.zstd_compressor.as_mut();
+    }
+
+    fn decompressor(&mut self) -> Option<&mut zstd::bulk::Decompressor<'static>> {
+        if self.zstd_decompressor.is_none() {
+            let decompressor = match &self.dictionary {
+                Some(dictionary) => zstd::bulk::Decompressor::with_dictionary(dictionary),
+                None => zstd::bulk::Decompressor::new(),
+            };
+            self.zstd_decompressor = decompressor.ok();
+        }
+        return self.zstd_decompressor.as_mut();
+    }
+
+    // writes the compressed message with it's header to output, returns the length
+    pub fn compress(&mut self, data: &[u8], output: &mut Vec<u8>) -> usize {
+        let bound = match self.codec {
+            COMPRESSION_LZ4 => lz4_flex::block::get_maximum_output_size(data.len()),
+            _ => zstd::zstd_safe::compress_bound(data.len()),
+        };
+        if output.len() < COMPRESSION_HEADER_SIZE + bound {
+            output.resize(COMPRESSION_HEADER_SIZE + bound, 0);
+        }
+
+        let compressed = match self.codec {
+            COMPRESSION_LZ4 => lz4_flex::block::compress_into(data, &mut output[COMPRESSION_HEADER_SIZE..]).ok(),
+            COMPRESSION_ZSTD => match self.compressor() {
+                Some(compressor) => compressor.compress_to_buffer(data, &mut output[COMPRESSION_HEADER_SIZE..]).ok(),
+                None => None,
+            },
+            _ => None,
+        };
+
+        let mut codec = self.codec as u8;
+        let mut length = match compressed {
+            Some(compressed_len) => compressed_len,
+            None => data.len(),
+        };
+        if compressed.is_none() || length >= data.len() {
+            codec = CODEC_RAW;
+            length = data.len();
+            output[COMPRESSION_HEADER_SIZE..COMPRESSION_HEADER_SIZE + length].copy_from_slice(data);
+        }
+
+        let mut writer = IntBuffer { index: 0 };
+        writer.write_u8(codec, output);
+        writer.write_u32(data.len() as u32, output);
+        return COMPRESSION_HEADER_SIZE + length;
+    }
+
+    // decompresses into output, None if the message is malformed or larger then max_message_size
+    pub fn decompress(&mut self, data: &[u8], output: &mut Vec<u8>) -> Option<usize> {
+        if data.len() < COMPRESSION_HEADER_SIZE {
+            return None;
+        }
+
+        let mut reader = IntBuffer { index: 0 };
+        let codec = reader.read_u8(data);
+        let length = reader.read_u32(data) as usize;
+        if length > self.max_message_size {
+            return None;
+        }
+        if output.len() < length {
+            output.resize(length, 0);
+        }
+
+        let body = &data[COMPRESSION_HEADER_SIZE..];
+        let decompressed = match codec as u32 {
+            COMPRESSION_LZ4 => lz4_flex::block::decompress_into(body, &mut output[0..length]).ok(),
+            COMPRESSION_ZSTD => match self.decompressor() {
+                Some(decompressor) => decompressor.decompress_to_buffer(body, &mut output[0..length]).ok(),
+                None => None,
+            },
+            _ => {
+                if body.len() != length {
+                    return None;
+                }
+                output[0..length].copy_from_slice(body);
+                Some(length)
+            }
+        };
+
+        if decompressed != Some(length) {
+            return None;
+        }
+        return decompressed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..2000).map(|i| (i % 16) as u8).collect();
+        let dictionary: Vec<u8> = (0..256).map(|i| (i % 16) as u8).collect();
+
+        for codec in [COMPRESSION_LZ4, COMPRESSION_ZSTD] {
+            let mut compression = Compression::create(codec, 4096);
+            if codec == COMPRESSION_ZSTD {
+                compression.set_dictionary(Some(dictionary.clone()));
+            }
+            let mut compressed: Vec<u8> = Vec::new();
+            let mut decompressed: Vec<u8> = Vec::new();
+
+            let len = compression.compress(&data, &mut compressed);
+            assert!(len < data.len() / 4);
+            assert_eq!(Some(data.len()), compression.decompress(&compressed[0..len], &mut decompressed));
+            assert_eq!(data[..], decompressed[0..data.len()]);
+
+            // incompressible is sent raw
+            let noise: Vec<u8> = (0..64).map(|i| (i * 131 % 251) as u8).collect();
+            let len = compression.compress(&noise, &mut compressed);
+            assert_eq!(COMPRESSION_HEADER_SIZE + noise.len(), len);
+            assert_eq!(Some(noise.len()), compression.decompress(&compressed[0..len], &mut decompressed));
+
+            // too large
+            compression.max_message_size = 100;
+            let len = compression.compress(&data, &mut compressed);
+            assert_eq!(None, compression.decompress(&compressed[0..len], &mut decompressed));
+        }
+    }
+}
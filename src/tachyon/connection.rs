@@ -8,6 +8,10 @@ pub struct Connection {
     pub tachyon_id: u16,
     pub received_at: u64,
     pub since_last_received: u64,
+    // smallest of ours and the other side's max datagram size, ours until settings are exchanged
+    pub max_datagram_size: u32,
+    // discovered path mtu, 0 until discovery completes or when it's not enabled
+    pub mtu: u32,
 }
 
 impl Connection {
@@ -18,6 +22,8 @@ impl Connection {
             tachyon_id,
             received_at: 0,
             since_last_received: 0,
+            max_datagram_size: 0,
+            mtu: 0,
         };
         return conn;
     }
@@ -1,17 +1,29 @@
-use std::time::Instant;
+use std::{io, time::Instant};
 
+use super::channel::{ChannelConfig, ChannelMismatch};
 use super::connection::{Connection, Identity};
 use super::header::{
-    ConnectionHeader, MESSAGE_TYPE_IDENTITY_LINKED, MESSAGE_TYPE_IDENTITY_UNLINKED,
-    MESSAGE_TYPE_LINK_IDENTITY, MESSAGE_TYPE_UNLINK_IDENTITY,
+    ConnectionHeader, MtuProbeHeader, SettingsChannel, SettingsHeader, SETTINGS_CHANNEL_SIZE, MESSAGE_TYPE_IDENTITY_LINKED, MESSAGE_TYPE_IDENTITY_UNLINKED,
+    MESSAGE_TYPE_LINK_IDENTITY, MESSAGE_TYPE_MTU_PROBE, MESSAGE_TYPE_MTU_PROBE_ACK, MESSAGE_TYPE_SETTINGS,
+    MESSAGE_TYPE_UNLINK_IDENTITY,
 };
+use super::event::{DisconnectReason, Event};
+use super::int_buffer::IntBuffer;
+use super::mtu::MtuDiscovery;
 use super::network_address::NetworkAddress;
-use super::Tachyon;
+use super::pool::SendTarget;
+use super::tachyon_socket::TachyonSocket;
+use super::{Tachyon, MAX_DATAGRAM_SIZE_DEFAULT, MAX_DATAGRAM_SIZE_MIN};
 
 const IDENTITY_SEND_INTERVAL: u128 = 300;
+const SETTINGS_SEND_INTERVAL: u128 = 300;
+const SETTINGS_MESSAGE_SIZE: usize = 5;
+const MTU_PROBE_HEADER_SIZE: usize = 3;
 
 pub const CONNECTION_ADDED_EVENT: u8 = 1;
 pub const CONNECTION_REMOVED_EVENT: u8 = 2;
+// a channel is configured differently on the other side, see take_channel_mismatch
+pub const CHANNEL_MISMATCH_EVENT: u8 = 3;
 
 pub const LINK_IDENTITY_EVENT: u8 = 1;
 pub const UNLINK_IDENTITY_EVENT: u8 = 2;
@@ -21,10 +33,15 @@ pub const IDENTITY_UNLINKED_EVENT: u8 = 4;
 pub type ConnectionEventCallback = unsafe extern "C" fn(action: u8, connection: Connection);
 pub type IdentityEventCallback = unsafe extern "C" fn(action: u8, connection: Connection);
 
+// closures for Rust users, called after the ffi callback when both are set.  Send because pool servers
+// receive on other threads.
+pub type ConnectionEventHandler = Box<dyn FnMut(u8, Connection) + Send>;
+pub type IdentityEventHandler = Box<dyn FnMut(u8, Connection) + Send>;
+
 impl Tachyon {
     // setting identity removes any associated connection
     pub fn set_identity(&mut self, id: u32, session_id: u32) {
-        self.remove_connection_by_identity(id);
+        self.remove_connection_by_identity(id, DisconnectReason::IdentityChanged);
 
         if session_id == 0 {
             self.identities.remove(&id);
@@ -37,14 +54,22 @@ impl Tachyon {
         let mut conn = Connection::create(address, self.id);
         conn.identity = identity;
         conn.received_at = self.time_since_start();
+        // larger datagrams wait for the settings exchange
+        conn.max_datagram_size = std::cmp::min(self.config.get_max_datagram_size(), MAX_DATAGRAM_SIZE_DEFAULT) as u32;
         self.connections.insert(address, conn);
         self.create_configured_channels(address);
         self.fire_connection_event(CONNECTION_ADDED_EVENT, address);
+        self.push_event(Event::Connected { address, identity: identity.id });
     }
 
-    fn remove_connection(&mut self, address: NetworkAddress) {
-        self.connections.remove(&address);
+    fn remove_connection(&mut self, address: NetworkAddress, reason: DisconnectReason) {
+        if let Some(conn) = self.connections.remove(&address) {
+            self.push_event(Event::Disconnected { address, identity: conn.identity.id, reason });
+        }
+        self.mtu_discovery.remove(&address);
+        self.send_buckets.remove(&address);
         self.remove_configured_channels(address);
+        self.topics.unsubscribe_all(SendTarget { identity_id: 0, address });
         self.fire_connection_event(CONNECTION_REMOVED_EVENT, address);
     }
 
@@ -76,26 +101,55 @@ impl Tachyon {
         return list;
     }
 
-    pub fn fire_identity_event(&self, event_id: u8, address: NetworkAddress, id: u32, session_id: u32) {
+    pub fn set_connection_event_handler<F>(&mut self, handler: F) where F: FnMut(u8, Connection) + Send + 'static {
+        self.connection_event_handler = Some(Box::new(handler));
+    }
+
+    pub fn set_identity_event_handler<F>(&mut self, handler: F) where F: FnMut(u8, Connection) + Send + 'static {
+        self.identity_event_handler = Some(Box::new(handler));
+    }
+
+    pub fn fire_identity_event(&mut self, event_id: u8, address: NetworkAddress, id: u32, session_id: u32) {
+        // the client's own identity for the linked/unlinked replies
+        let (identity, identity_session_id) = match event_id {
+            IDENTITY_LINKED_EVENT | IDENTITY_UNLINKED_EVENT => (self.identity.id, self.identity.session_id),
+            _ => (id, session_id),
+        };
+        if event_id == LINK_IDENTITY_EVENT || event_id == IDENTITY_LINKED_EVENT {
+            self.push_event(Event::IdentityLinked { address, identity, session_id: identity_session_id });
+        } else {
+            self.push_event(Event::IdentityUnlinked { address, identity, session_id: identity_session_id });
+        }
+
+        if self.identity_event_callback.is_none() && self.identity_event_handler.is_none() {
+            return;
+        }
+
+        let mut conn = Connection::create(address, self.id);
+        conn.identity = Identity {id, session_id, linked: 0 };
+        if event_id == IDENTITY_LINKED_EVENT {
+           conn.identity.linked = 1; 
+        }
         if let Some(callback) = self.identity_event_callback {
-            let mut conn = Connection::create(address, self.id);
-            conn.identity = Identity {id, session_id, linked: 0 };
-            if event_id == IDENTITY_LINKED_EVENT {
-               conn.identity.linked = 1; 
-            }
             unsafe {
                 callback(event_id, conn);
             }
         }
+        if let Some(handler) = &mut self.identity_event_handler {
+            handler(event_id, conn);
+        }
     }
 
-    pub fn fire_connection_event(&self, event_id: u8, address: NetworkAddress) {
+    pub fn fire_connection_event(&mut self, event_id: u8, address: NetworkAddress) {
+        let conn = Connection::create(address, self.id);
         if let Some(callback) = self.connection_event_callback {
-            let conn = Connection::create(address, self.id);
             unsafe {
                 callback(event_id, conn);
             }
         }
+        if let Some(handler) = &mut self.connection_event_handler {
+            handler(event_id, conn);
+        }
     }
 
     // run when use_identity is not set
@@ -130,7 +184,7 @@ impl Tachyon {
 
     
 
-    pub fn remove_connection_by_identity(&mut self, id: u32) {
+    pub fn remove_connection_by_identity(&mut self, id: u32, reason: DisconnectReason) {
         let mut addresses: Vec<NetworkAddress> = Vec::new();
 
         for conn in self.connections.values_mut() {
@@ -139,7 +193,7 @@ impl Tachyon {
             }
         }
         for addr in addresses {
-            self.remove_connection(addr);
+            self.remove_connection(addr, reason);
         }
     }
 
@@ -156,7 +210,7 @@ impl Tachyon {
                 return true;
             }
 
-            self.remove_connection_by_identity(id);
+            self.remove_connection_by_identity(id, DisconnectReason::Relinked);
             let identity = Identity {
                 id: id,
                 session_id: session_id,
@@ -176,7 +230,7 @@ impl Tachyon {
                 return false;
             }
 
-            self.remove_connection_by_identity(id);
+            self.remove_connection_by_identity(id, DisconnectReason::Unlinked);
             self.identity_to_address_map.remove(&id);
             self.send_identity_unlinked(address);
             return true;
@@ -213,6 +267,222 @@ impl Tachyon {
         }
     }
 
+    pub fn get_max_datagram_size(&self, address: NetworkAddress) -> usize {
+        if let Some(conn) = self.connections.get(&address) {
+            return conn.max_datagram_size as usize;
+        }
+        return self.config.get_max_datagram_size();
+    }
+
+    // client sends it's settings until the server replies with it's own
+    pub fn client_settings_update(&mut self) {
+        if self.socket.socket.is_none() {
+            return;
+        }
+
+        if self.socket.is_server {
+            return;
+        }
+
+        if self.settings_confirmed || !self.can_send() {
+            return;
+        }
+
+        let since_last = Instant::now() - self.last_settings_request;
+        if since_last.as_millis() > SETTINGS_SEND_INTERVAL {
+            self.last_settings_request = Instant::now();
+            self.send_settings(NetworkAddress::default());
+        }
+    }
+
+    pub fn on_receive_settings(&mut self, address: NetworkAddress, received_len: usize) {
+        if received_len < SETTINGS_MESSAGE_SIZE {
+            return;
+        }
+        let header = SettingsHeader::read(&self.socket_receive_buffer);
+        let remote_size = header.max_datagram_size as usize;
+        let max_datagram_size = std::cmp::min(self.config.get_max_datagram_size(), remote_size);
+
+        if let Some(conn) = self.connections.get_mut(&address) {
+            conn.max_datagram_size = max_datagram_size as u32;
+        } else {
+            return;
+        }
+        self.apply_max_datagram_size(address);
+
+        // the negotiated size is the upper bound for discovery
+        if self.config.use_mtu_discovery == 1 && !self.mtu_discovery.contains_key(&address) {
+            self.mtu_discovery.insert(address, MtuDiscovery::create(MAX_DATAGRAM_SIZE_MIN, max_datagram_size));
+        }
+
+        if received_len > SETTINGS_MESSAGE_SIZE {
+            self.negotiate_channels(address, received_len);
+        }
+
+        if self.socket.is_server {
+            self.send_settings(address);
+        } else {
+            self.settings_confirmed = true;
+        }
+    }
+
+    // Settings carry the sender's channels after the header.  Channels only they have are created for the connection,
+    // negotiated channels they no longer have are removed, and ones configured differently are reported.
+    fn negotiate_channels(&mut self, address: NetworkAddress, received_len: usize) {
+        let mut reader = IntBuffer { index: SETTINGS_MESSAGE_SIZE };
+        let count = reader.read_u8(&self.socket_receive_buffer) as usize;
+        if received_len < SETTINGS_MESSAGE_SIZE + 1 + count * SETTINGS_CHANNEL_SIZE {
+            return;
+        }
+
+        let mut remote_channels: Vec<SettingsChannel> = Vec::new();
+        for _ in 0..count {
+            remote_channels.push(SettingsChannel::read(&self.socket_receive_buffer, &mut reader));
+        }
+
+        let mut mismatched = false;
+        for remote in &remote_channels {
+            if remote.id == 0 {
+                continue;
+            }
+
+            match self.channel_config.get(&remote.id) {
+                Some(config) => {
+                    let local = config.to_settings(remote.id);
+                    // neither side can tell which delivery the other expects, so both stop sending on it
+                    if let Some(channel) = self.channels.get_mut(&(address, remote.id)) {
+                        channel.mismatched = local != *remote;
+                    }
+                    if local != *remote {
+                        let mismatch = ChannelMismatch {
+                            address,
+                            channel: remote.id,
+                            local_ordered: local.ordered,
+                            local_mode: local.mode,
+                            remote_ordered: remote.ordered,
+                            remote_mode: remote.mode,
+                            local_compression: local.compression,
+                            remote_compression: remote.compression,
+                        };
+                        self.channel_mismatches.push_back(mismatch);
+                        mismatched = true;
+                    }
+                }
+                None => {
+                    if !self.channels.contains_key(&(address, remote.id)) {
+                        self.create_negotiated_channel(address, remote.id, ChannelConfig::from_settings(*remote));
+                    }
+                }
+            }
+        }
+
+        let channel_config = &self.channel_config;
+        self.channels.retain(|key, _| {
+            if key.0 != address || channel_config.contains_key(&key.1) {
+                return true;
+            }
+            return remote_channels.iter().any(|remote| remote.id == key.1);
+        });
+
+        if mismatched {
+            self.fire_connection_event(CHANNEL_MISMATCH_EVENT, address);
+        }
+    }
+
+    // tell the other side our channels changed
+    pub fn announce_channels(&mut self) {
+        if self.socket.is_server {
+            let addresses: Vec<NetworkAddress> = self.connections.keys().copied().collect();
+            for address in addresses {
+                self.send_settings(address);
+            }
+        } else {
+            // resent until the server answers
+            self.settings_confirmed = false;
+        }
+    }
+
+    pub fn mtu_update(&mut self) {
+        if self.mtu_discovery.len() == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut probes: Vec<(NetworkAddress, u16, usize)> = Vec::new();
+        let mut completed: Vec<(NetworkAddress, usize)> = Vec::new();
+        for (address, discovery) in self.mtu_discovery.iter_mut() {
+            if let Some(size) = discovery.next_probe(now) {
+                probes.push((*address, discovery.probe_id, size));
+            } else if discovery.complete {
+                completed.push((*address, discovery.mtu()));
+            }
+        }
+
+        // refused as too large fails right away, other send errors are retried after the probe timeout
+        for (address, probe_id, size) in probes {
+            if let Err(error) = self.send_mtu_probe(address, probe_id, size) {
+                if TachyonSocket::is_message_too_long(&error) {
+                    if let Some(discovery) = self.mtu_discovery.get_mut(&address) {
+                        discovery.on_probe_failed();
+                    }
+                }
+            }
+        }
+
+        for (address, mtu) in completed {
+            self.mtu_discovery.remove(&address);
+            if let Some(conn) = self.connections.get_mut(&address) {
+                conn.mtu = mtu as u32;
+            }
+            self.apply_max_datagram_size(address);
+        }
+    }
+
+    fn send_mtu_probe(&self, address: NetworkAddress, probe_id: u16, size: usize) -> io::Result<usize> {
+        let mut header = MtuProbeHeader::default();
+        header.message_type = MESSAGE_TYPE_MTU_PROBE;
+        header.probe_id = probe_id;
+        let mut send_buffer: Vec<u8> = vec![0; size];
+        header.write(&mut send_buffer);
+        return self.socket.send_probe(address, &send_buffer, size);
+    }
+
+    pub fn on_receive_mtu_probe(&mut self, address: NetworkAddress, received_len: usize) {
+        if received_len < MTU_PROBE_HEADER_SIZE {
+            return;
+        }
+        let mut header = MtuProbeHeader::read(&self.socket_receive_buffer);
+        header.message_type = MESSAGE_TYPE_MTU_PROBE_ACK;
+        let mut send_buffer: Vec<u8> = vec![0; MTU_PROBE_HEADER_SIZE];
+        header.write(&mut send_buffer);
+        self.socket.send_to(address, &send_buffer, send_buffer.len());
+    }
+
+    pub fn on_receive_mtu_probe_ack(&mut self, address: NetworkAddress, received_len: usize) {
+        if received_len < MTU_PROBE_HEADER_SIZE {
+            return;
+        }
+        let header = MtuProbeHeader::read(&self.socket_receive_buffer);
+        if let Some(discovery) = self.mtu_discovery.get_mut(&address) {
+            discovery.on_probe_ack(header.probe_id);
+        }
+    }
+
+    fn send_settings(&self, address: NetworkAddress) {
+        let mut header = SettingsHeader::default();
+        header.message_type = MESSAGE_TYPE_SETTINGS;
+        header.max_datagram_size = self.config.get_max_datagram_size() as u32;
+
+        let count = std::cmp::min(self.channel_config.len(), u8::MAX as usize);
+        let mut send_buffer: Vec<u8> = vec![0; SETTINGS_MESSAGE_SIZE + 1 + count * SETTINGS_CHANNEL_SIZE];
+        let mut writer = IntBuffer { index: header.write(&mut send_buffer) };
+        writer.write_u8(count as u8, &mut send_buffer);
+        for (channel_id, config) in self.channel_config.iter().take(count) {
+            config.to_settings(*channel_id).write(&mut send_buffer, &mut writer);
+        }
+        self.socket.send_to(address, &send_buffer, send_buffer.len());
+    }
+
     pub fn can_send(&self) -> bool {
         if self.socket.is_server {
             return true;
@@ -259,8 +529,11 @@ mod tests {
 
     use crate::tachyon::{
         connection::Identity, network_address::NetworkAddress, tachyon_test::TachyonTest, Tachyon,
-        TachyonConfig,
+        TachyonConfig, event::{DisconnectReason, Event},
     };
+    use std::sync::{Arc, Mutex};
+
+    use super::{CONNECTION_ADDED_EVENT, CONNECTION_REMOVED_EVENT, LINK_IDENTITY_EVENT};
 
     #[test]
     fn test_connect() {
@@ -307,6 +580,52 @@ mod tests {
         assert_eq!(0, server.get_channel_count(address));
     }
 
+    #[test]
+    fn test_connection_events() {
+        let address = NetworkAddress::localhost(100);
+        let changed_address = NetworkAddress::localhost(200);
+
+        let config = TachyonConfig::default();
+        let mut server = Tachyon::create(config);
+        server.set_identity(1, 10);
+        server.try_link_identity(address, 1, 10);
+        server.try_link_identity(changed_address, 1, 10);
+        server.try_unlink_identity(changed_address, 1, 10);
+
+        let events: Vec<Event> = server.events.drain(..).collect();
+        assert_eq!(vec![
+            Event::Connected { address, identity: 1 },
+            Event::Disconnected { address, identity: 1, reason: DisconnectReason::Relinked },
+            Event::Connected { address: changed_address, identity: 1 },
+            Event::Disconnected { address: changed_address, identity: 1, reason: DisconnectReason::Unlinked },
+        ], events);
+    }
+
+    #[test]
+    fn test_event_handlers() {
+        let address = NetworkAddress::localhost(100);
+        let received: Arc<Mutex<Vec<(u8, u32)>>> = Arc::new(Mutex::new(Vec::new()));
+
+        let config = TachyonConfig::default();
+        let mut server = Tachyon::create(config);
+        let connection_received = received.clone();
+        server.set_connection_event_handler(move |event_id, connection| {
+            connection_received.lock().unwrap().push((event_id, connection.address.port));
+        });
+        let identity_received = received.clone();
+        server.set_identity_event_handler(move |event_id, connection| {
+            identity_received.lock().unwrap().push((event_id, connection.identity.id));
+        });
+
+        server.set_identity(1, 10);
+        server.try_link_identity(address, 1, 10);
+        server.fire_identity_event(LINK_IDENTITY_EVENT, address, 1, 10);
+        server.try_unlink_identity(address, 1, 10);
+
+        assert_eq!(vec![(CONNECTION_ADDED_EVENT, 100), (LINK_IDENTITY_EVENT, 1), (CONNECTION_REMOVED_EVENT, 100)],
+            *received.lock().unwrap());
+    }
+
     #[test]
     fn test_validate_and_update_connection() {
         let address = NetworkAddress::localhost(100);
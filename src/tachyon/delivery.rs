@@ -0,0 +1,189 @@
+use std::collections::VecDeque;
+
+use super::{network_address::NetworkAddress, send_buffer_manager::SendBufferManager, sequence::Sequence};
+
+pub const DELIVERY_DELIVERED: u8 = 1;
+pub const DELIVERY_EXPIRED: u8 = 2;
+
+pub type DeliveryEventCallback = unsafe extern "C" fn(event: DeliveryEvent);
+
+// sequence is the one returned in the send result header, for fragmented messages the first fragment sequence
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct DeliveryEvent {
+    pub address: NetworkAddress,
+    pub channel: u8,
+    pub status: u8,
+    pub sequence: u16,
+    pub fragment_count: u16,
+}
+
+#[derive(Clone, Copy)]
+struct PendingDelivery {
+    sequence: u16,
+    last_sequence: u16,
+    fragment_count: u16,
+}
+
+// Tracks sent reliable messages until they are covered by a cumulative ack (delivered), or until one of their
+// send buffers is gone before being acked (expired/overwritten, so it can no longer be resent).
+pub struct DeliveryTracker {
+    pending: VecDeque<PendingDelivery>,
+    pub events: VecDeque<DeliveryEvent>,
+}
+
+impl DeliveryTracker {
+    pub fn default() -> Self {
+        let tracker = DeliveryTracker {
+            pending: VecDeque::new(),
+            events: VecDeque::new(),
+        };
+        return tracker;
+    }
+
+    pub fn pending_count(&self) -> usize {
+        return self.pending.len();
+    }
+
+    pub fn track(&mut self, sequence: u16, fragment_count: u16) {
+        let mut last_sequence = sequence;
+        for _ in 1..fragment_count {
+            last_sequence = Sequence::next_sequence(last_sequence);
+        }
+
+        let pending = PendingDelivery {
+            sequence,
+            last_sequence,
+            fragment_count,
+        };
+        self.pending.push_back(pending);
+    }
+
+    // Pending is in send order so acked messages are always at the front.  Acks also cover sequences the receiver
+    // skipped and ones that expired, those are lost and the message expired.
+    pub fn on_ack(&mut self, send_buffers: &SendBufferManager, address: NetworkAddress, channel: u8) {
+        while let Some(pending) = self.pending.front() {
+            if !Sequence::is_equal_to_or_less_than(pending.last_sequence, send_buffers.acked_sequence) {
+                break;
+            }
+            let pending = *pending;
+            self.pending.pop_front();
+            if DeliveryTracker::is_lost(&pending, send_buffers) {
+                self.push_event(pending, DELIVERY_EXPIRED, address, channel);
+            } else {
+                self.push_event(pending, DELIVERY_DELIVERED, address, channel);
+            }
+        }
+    }
+
+    fn is_lost(pending: &PendingDelivery, send_buffers: &SendBufferManager) -> bool {
+        let mut seq = pending.sequence;
+        for _ in 0..pending.fragment_count {
+            if send_buffers.is_lost(seq) {
+                return true;
+            }
+            seq = Sequence::next_sequence(seq);
+        }
+        return false;
+    }
+
+    pub fn expire(&mut self, send_buffers: &SendBufferManager, address: NetworkAddress, channel: u8) {
+        if self.pending.len() == 0 {
+            return;
+        }
+
+        let mut expired: Vec<PendingDelivery> = Vec::new();
+        self.pending.retain(|pending| {
+            if DeliveryTracker::is_undeliverable(pending, send_buffers) {
+                expired.push(*pending);
+                return false;
+            }
+            return true;
+        });
+
+        for pending in expired {
+            self.push_event(pending, DELIVERY_EXPIRED, address, channel);
+        }
+    }
+
+    fn is_undeliverable(pending: &PendingDelivery, send_buffers: &SendBufferManager) -> bool {
+        let mut seq = pending.sequence;
+        for _ in 0..pending.fragment_count {
+            // reserved by a stream channel but not sent yet
+            if Sequence::is_greater_then(seq, send_buffers.current_sequence) {
+                return false;
+            }
+            if !send_buffers.is_acked(seq) && !send_buffers.is_buffered(seq) {
+                return true;
+            }
+            seq = Sequence::next_sequence(seq);
+        }
+        return false;
+    }
+
+    fn push_event(&mut self, pending: PendingDelivery, status: u8, address: NetworkAddress, channel: u8) {
+        let event = DeliveryEvent {
+            address,
+            channel,
+            status,
+            sequence: pending.sequence,
+            fragment_count: pending.fragment_count,
+        };
+        self.events.push_back(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tachyon::{network_address::NetworkAddress, send_buffer_manager::SendBufferManager};
+
+    use super::*;
+
+    #[test]
+    fn test_delivered_and_expired() {
+        let mut tracker = DeliveryTracker::default();
+        let mut send_buffers = SendBufferManager::default();
+        let address = NetworkAddress::default();
+
+        // single message at 1, fragmented message 2..4
+        send_buffers.create_send_buffer(32);
+        tracker.track(1, 1);
+        for _ in 0..3 {
+            send_buffers.create_send_buffer(32);
+        }
+        tracker.track(2, 3);
+
+        // partial ack of the fragmented message only delivers the first
+        send_buffers.ack(3);
+        tracker.on_ack(&send_buffers, address, 1);
+        assert_eq!(1, tracker.events.len());
+        assert_eq!(1, tracker.pending_count());
+
+        // partially acked is still deliverable
+        tracker.expire(&send_buffers, address, 1);
+        assert_eq!(1, tracker.pending_count());
+
+        let event = tracker.events.pop_front().unwrap();
+        assert_eq!(DELIVERY_DELIVERED, event.status);
+        assert_eq!(1, event.sequence);
+
+        // last fragment buffer gone without an ack
+        send_buffers.buffers.remove(4);
+        tracker.expire(&send_buffers, address, 1);
+        assert_eq!(0, tracker.pending_count());
+
+        let event = tracker.events.pop_front().unwrap();
+        assert_eq!(DELIVERY_EXPIRED, event.status);
+        assert_eq!(2, event.sequence);
+        assert_eq!(3, event.fragment_count);
+
+        // acked, but the receiver skipped it
+        send_buffers.create_send_buffer(32);
+        tracker.track(5, 1);
+        send_buffers.set_lost(5);
+        send_buffers.ack(5);
+        tracker.on_ack(&send_buffers, address, 1);
+        assert_eq!(DELIVERY_EXPIRED, tracker.events.pop_front().unwrap().status);
+    }
+}
@@ -0,0 +1,484 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    int_buffer::IntBuffer,
+    network_address::NetworkAddress,
+    tachyon_socket::{SocketReceiveResult, TachyonSocket},
+};
+
+pub const DISCOVERY_MAGIC: u32 = 0x54434451;
+pub const DISCOVERY_QUERY: u8 = 1;
+pub const DISCOVERY_RESPONSE: u8 = 2;
+
+// magic, kind, app id, nonce
+pub const DISCOVERY_HEADER_SIZE: usize = 13;
+// header, port, info length
+pub const DISCOVERY_RESPONSE_HEADER_SIZE: usize = DISCOVERY_HEADER_SIZE + 6;
+pub const DISCOVERY_MAX_INFO: usize = 1024;
+// Queries are padded to the largest response and servers don't answer a query shorter then their response, so
+// a spoofed query can't be used to send anyone more then it took to send.
+pub const DISCOVERY_QUERY_SIZE: usize = DISCOVERY_RESPONSE_HEADER_SIZE + DISCOVERY_MAX_INFO;
+
+// unanswered queries are forgotten after this
+const QUERY_EXPIRE: Duration = Duration::from_secs(10);
+
+// LAN discovery on it's own socket, separate from the Tachyon being discovered.  Clients send a query to a
+// broadcast address, a multicast group or a single host, servers with the same app id answer with the port
+// their Tachyon listens on and an info blob the app defines.  Clients collect the responses with the time
+// each took to arrive.
+
+pub struct DiscoveryServer {
+    pub socket: TachyonSocket,
+    pub app_id: u32,
+    // sent back to clients, 0 answers with the discovery port
+    pub port: u32,
+    info: Vec<u8>,
+    receive_buffer: Vec<u8>,
+    send_buffer: Vec<u8>,
+}
+
+impl DiscoveryServer {
+    pub fn create(app_id: u32, port: u32, info: &[u8]) -> Self {
+        let mut server = DiscoveryServer {
+            socket: TachyonSocket::create(),
+            app_id,
+            port,
+            info: Vec::new(),
+            receive_buffer: vec![0; DISCOVERY_QUERY_SIZE],
+            send_buffer: vec![0; DISCOVERY_RESPONSE_HEADER_SIZE + DISCOVERY_MAX_INFO],
+        };
+        server.set_info(info);
+        return server;
+    }
+
+    // bind to 0.0.0.0 and the discovery port to receive broadcasts
+    pub fn bind(&mut self, address: NetworkAddress) -> io::Result<()> {
+        return self.socket.try_bind_socket(address);
+    }
+
+    pub fn join_multicast(&mut self, group: NetworkAddress) -> io::Result<()> {
+        return self.socket.join_multicast(group);
+    }
+
+    // truncated to DISCOVERY_MAX_INFO
+    pub fn set_info(&mut self, info: &[u8]) {
+        let length = std::cmp::min(info.len(), DISCOVERY_MAX_INFO);
+        self.info = info[0..length].to_vec();
+    }
+
+    // answers every query waiting, returns how many
+    pub fn update(&mut self) -> u32 {
+        let mut answered = 0;
+        loop {
+            let (bytes_received, address) = match self.socket.receive(&mut self.receive_buffer, 0, false) {
+                SocketReceiveResult::Success { bytes_received, network_address } => (bytes_received, network_address),
+                SocketReceiveResult::Dropped => {
+                    continue;
+                }
+                _ => {
+                    return answered;
+                }
+            };
+
+            let nonce = match read_header(&self.receive_buffer[0..bytes_received], DISCOVERY_QUERY, self.app_id) {
+                Some(nonce) => nonce,
+                None => {
+                    continue;
+                }
+            };
+
+            let length = DISCOVERY_RESPONSE_HEADER_SIZE + self.info.len();
+            if bytes_received < length {
+                continue;
+            }
+
+            let port = if self.port > 0 { self.port } else { self.socket.address.port };
+            let mut writer = IntBuffer { index: 0 };
+            write_header(&mut writer, &mut self.send_buffer, DISCOVERY_RESPONSE, self.app_id, nonce);
+            writer.write_u32(port, &mut self.send_buffer);
+            writer.write_u16(self.info.len() as u16, &mut self.send_buffer);
+            self.send_buffer[DISCOVERY_RESPONSE_HEADER_SIZE..length].copy_from_slice(&self.info);
+
+            if self.socket.send_to(address, &self.send_buffer, length) > 0 {
+                answered += 1;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DiscoveredServer {
+    // responder's ip and the port it answered with
+    pub address: NetworkAddress,
+    // query to response, the most recent one
+    pub latency_micros: u64,
+    pub info: Vec<u8>,
+}
+
+pub struct DiscoveryClient {
+    pub socket: TachyonSocket,
+    pub app_id: u32,
+    next_nonce: u32,
+    queries: FxHashMap<u32, Instant>,
+    servers: Vec<DiscoveredServer>,
+    receive_buffer: Vec<u8>,
+    send_buffer: Vec<u8>,
+}
+
+impl DiscoveryClient {
+    pub fn create(app_id: u32) -> Self {
+        let client = DiscoveryClient {
+            socket: TachyonSocket::create(),
+            app_id,
+            next_nonce: 1,
+            queries: FxHashMap::default(),
+            servers: Vec::new(),
+            receive_buffer: vec![0; DISCOVERY_RESPONSE_HEADER_SIZE + DISCOVERY_MAX_INFO],
+            send_buffer: vec![0; DISCOVERY_QUERY_SIZE],
+        };
+        return client;
+    }
+
+    // any local port, with broadcast enabled
+    pub fn bind(&mut self) -> io::Result<()> {
+        self.socket.try_bind_socket(NetworkAddress::default())?;
+        return self.socket.set_broadcast(true);
+    }
+
+    // target is a broadcast address, a multicast group or a single host, with the discovery port
+    pub fn query(&mut self, target: NetworkAddress) -> io::Result<()> {
+        self.queries.retain(|_, sent_at| sent_at.elapsed() < QUERY_EXPIRE);
+
+        let nonce = self.next_nonce;
+        self.next_nonce = self.next_nonce.wrapping_add(1);
+
+        let mut writer = IntBuffer { index: 0 };
+        write_header(&mut writer, &mut self.send_buffer, DISCOVERY_QUERY, self.app_id, nonce);
+        if self.socket.send_to(target, &self.send_buffer, DISCOVERY_QUERY_SIZE) == 0 {
+            return Err(io::Error::other("discovery query not sent"));
+        }
+        self.queries.insert(nonce, Instant::now());
+        return Ok(());
+    }
+
+    pub fn query_broadcast(&mut self, port: u32) -> io::Result<()> {
+        let mut address = NetworkAddress::broadcast(0);
+        address.port = port;
+        return self.query(address);
+    }
+
+    // reads every response waiting, returns how many
+    pub fn update(&mut self) -> u32 {
+        let mut count = 0;
+        loop {
+            let (bytes_received, address) = match self.socket.receive(&mut self.receive_buffer, 0, false) {
+                SocketReceiveResult::Success { bytes_received, network_address } => (bytes_received, network_address),
+                SocketReceiveResult::Dropped => {
+                    continue;
+                }
+                _ => {
+                    return count;
+                }
+            };
+
+            let data = &self.receive_buffer[0..bytes_received];
+            let nonce = match read_header(data, DISCOVERY_RESPONSE, self.app_id) {
+                Some(nonce) => nonce,
+                None => {
+                    continue;
+                }
+            };
+            let sent_at = match self.queries.get(&nonce) {
+                Some(sent_at) => *sent_at,
+                None => {
+                    continue;
+                }
+            };
+            if data.len() < DISCOVERY_RESPONSE_HEADER_SIZE {
+                continue;
+            }
+
+            let mut reader = IntBuffer { index: DISCOVERY_HEADER_SIZE };
+            let port = reader.read_u32(data);
+            let info_length = reader.read_u16(data) as usize;
+            if data.len() < DISCOVERY_RESPONSE_HEADER_SIZE + info_length {
+                continue;
+            }
+
+            let mut server_address = address;
+            if port > 0 {
+                server_address.port = port;
+            }
+            let server = DiscoveredServer {
+                address: server_address,
+                latency_micros: sent_at.elapsed().as_micros() as u64,
+                info: data[DISCOVERY_RESPONSE_HEADER_SIZE..DISCOVERY_RESPONSE_HEADER_SIZE + info_length].to_vec(),
+            };
+            match self.servers.iter_mut().find(|known| known.address == server_address) {
+                Some(known) => {
+                    *known = server;
+                }
+                None => {
+                    self.servers.push(server);
+                }
+            }
+            count += 1;
+        }
+    }
+
+    // queries and collects responses until the timeout passes
+    pub fn discover(&mut self, target: NetworkAddress, timeout: Duration) -> io::Result<&[DiscoveredServer]> {
+        let deadline = Instant::now() + timeout;
+        self.query(target)?;
+        loop {
+            self.update();
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            self.socket.wait_readable(deadline - now);
+        }
+        return Ok(&self.servers);
+    }
+
+    // in the order first discovered
+    pub fn get_servers(&self) -> &[DiscoveredServer] {
+        return &self.servers;
+    }
+
+    pub fn clear(&mut self) {
+        self.servers.clear();
+    }
+}
+
+fn write_header(writer: &mut IntBuffer, data: &mut [u8], kind: u8, app_id: u32, nonce: u32) {
+    writer.write_u32(DISCOVERY_MAGIC, data);
+    writer.write_u8(kind, data);
+    writer.write_u32(app_id, data);
+    writer.write_u32(nonce, data);
+}
+
+// the nonce, None if it's not a discovery message of this kind and app
+fn read_header(data: &[u8], kind: u8, app_id: u32) -> Option<u32> {
+    if data.len() < DISCOVERY_HEADER_SIZE {
+        return None;
+    }
+    let mut reader = IntBuffer { index: 0 };
+    if reader.read_u32(data) != DISCOVERY_MAGIC || reader.read_u8(data) != kind || reader.read_u32(data) != app_id {
+        return None;
+    }
+    return Some(reader.read_u32(data));
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_server_create(app_id: u32, port: u32, info: *const u8, info_length: i32, bind_address: *const NetworkAddress) -> *mut DiscoveryServer {
+    let info = if info.is_null() || info_length <= 0 { &[] } else { unsafe { std::slice::from_raw_parts(info, info_length as usize) } };
+    let address: NetworkAddress = unsafe { std::ptr::read(bind_address) };
+
+    let mut server = DiscoveryServer::create(app_id, port, info);
+    if server.bind(address).is_err() {
+        return std::ptr::null_mut();
+    }
+    let b = Box::new(server);
+    return Box::into_raw(b);
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_server_destroy(server: *mut DiscoveryServer) {
+    if !server.is_null() {
+        let _b = unsafe { Box::from_raw(server) };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_server_join_multicast(server_ptr: *mut DiscoveryServer, group: *const NetworkAddress) -> i32 {
+    let server = unsafe { &mut *server_ptr };
+    let group: NetworkAddress = unsafe { std::ptr::read(group) };
+    match server.join_multicast(group) {
+        Ok(()) => {
+            return 1;
+        }
+        Err(_) => {
+            return -1;
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_server_set_info(server_ptr: *mut DiscoveryServer, info: *const u8, info_length: i32) {
+    let server = unsafe { &mut *server_ptr };
+    let info = if info.is_null() || info_length <= 0 { &[] } else { unsafe { std::slice::from_raw_parts(info, info_length as usize) } };
+    server.set_info(info);
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_server_update(server_ptr: *mut DiscoveryServer) -> i32 {
+    let server = unsafe { &mut *server_ptr };
+    return server.update() as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_client_create(app_id: u32) -> *mut DiscoveryClient {
+    let mut client = DiscoveryClient::create(app_id);
+    if client.bind().is_err() {
+        return std::ptr::null_mut();
+    }
+    let b = Box::new(client);
+    return Box::into_raw(b);
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_client_destroy(client: *mut DiscoveryClient) {
+    if !client.is_null() {
+        let _b = unsafe { Box::from_raw(client) };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn discovery_client_query(client_ptr: *mut DiscoveryClient, target: *const NetworkAddress) -> i32 {
+    let client = unsafe { &mut *client_ptr };
+    let target: NetworkAddress = unsafe { std::ptr::read(target) };
+    match client.query(target) {
+        Ok(()) => {
+            return 1;
+        }
+        Err(_) => {
+            return -1;
+        }
+    }
+}
+
+// returns the number of servers discovered so far
+#[no_mangle]
+pub extern "C" fn discovery_client_update(client_ptr: *mut DiscoveryClient) -> i32 {
+    let client = unsafe { &mut *client_ptr };
+    client.update();
+    return client.get_servers().len() as i32;
+}
+
+// copies as much of the info as fits and returns it's full length, -1 if there is no server at index
+#[no_mangle]
+pub extern "C" fn discovery_client_get_server(client_ptr: *mut DiscoveryClient, index: i32, address: *mut NetworkAddress, latency_micros: *mut u64, info: *mut u8, info_length: i32) -> i32 {
+    let client = unsafe { &mut *client_ptr };
+    let server = match client.get_servers().get(index as usize) {
+        Some(server) if index >= 0 => server,
+        _ => {
+            return -1;
+        }
+    };
+
+    unsafe {
+        *address = server.address;
+        *latency_micros = server.latency_micros;
+    }
+    if !info.is_null() && info_length > 0 {
+        let length = std::cmp::min(server.info.len(), info_length as usize);
+        let slice = unsafe { std::slice::from_raw_parts_mut(info, length) };
+        slice.copy_from_slice(&server.info[0..length]);
+    }
+    return server.info.len() as i32;
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use super::*;
+
+    #[test]
+    #[serial]
+    fn test_discovery() {
+        let mut server = DiscoveryServer::create(7, 8001, b"arena 2/8");
+        server.bind(NetworkAddress::localhost(8301)).unwrap();
+        // another app on the same network
+        let mut other = DiscoveryServer::create(8, 0, b"other");
+        other.bind(NetworkAddress::localhost(8302)).unwrap();
+
+        let mut client = DiscoveryClient::create(7);
+        client.bind().unwrap();
+        client.query(NetworkAddress::localhost(8301)).unwrap();
+        client.query(NetworkAddress::localhost(8302)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(1, server.update());
+        assert_eq!(0, other.update());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(1, client.update());
+        let servers = client.get_servers();
+        assert_eq!(1, servers.len());
+        assert_eq!(NetworkAddress::localhost(8001), servers[0].address);
+        assert_eq!(b"arena 2/8".to_vec(), servers[0].info);
+        assert!(servers[0].latency_micros >= 20000);
+
+        // discover blocks while the server answers on another thread
+        server.set_info(b"arena 3/8");
+        let answer = std::thread::spawn(move || {
+            let start = Instant::now();
+            while start.elapsed() < Duration::from_millis(200) {
+                server.update();
+                server.socket.wait_readable(Duration::from_millis(10));
+            }
+        });
+        let servers = client.discover(NetworkAddress::localhost(8301), Duration::from_millis(100)).unwrap();
+        assert_eq!(1, servers.len());
+        assert_eq!(b"arena 3/8".to_vec(), servers[0].info);
+        answer.join().unwrap();
+
+        // an unpadded query isn't answered
+        let mut server = DiscoveryServer::create(7, 0, b"arena 2/8");
+        server.bind(NetworkAddress::localhost(8303)).unwrap();
+        let mut query: Vec<u8> = vec![0; DISCOVERY_HEADER_SIZE];
+        write_header(&mut IntBuffer { index: 0 }, &mut query, DISCOVERY_QUERY, 7, 1);
+        client.socket.send_to(NetworkAddress::localhost(8303), &query, DISCOVERY_HEADER_SIZE);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(0, server.update());
+    }
+
+    fn any_address(port: u32) -> NetworkAddress {
+        let mut address = NetworkAddress::default();
+        address.port = port;
+        return address;
+    }
+
+    #[test]
+    #[serial]
+    fn test_discovery_broadcast() {
+        let mut server = DiscoveryServer::create(7, 8001, b"lan");
+        server.bind(any_address(8304)).unwrap();
+
+        let mut client = DiscoveryClient::create(7);
+        client.bind().unwrap();
+        client.query_broadcast(8304).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(1, server.update());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(1, client.update());
+        assert_eq!(8001, client.get_servers()[0].address.port);
+        assert_eq!(b"lan".to_vec(), client.get_servers()[0].info);
+    }
+
+    #[test]
+    #[serial]
+    fn test_discovery_multicast() {
+        let group = NetworkAddress { a: 239, b: 255, c: 42, d: 99, port: 8305 };
+        let mut server = DiscoveryServer::create(7, 8001, b"group");
+        server.bind(any_address(8305)).unwrap();
+        server.join_multicast(group).unwrap();
+
+        let mut client = DiscoveryClient::create(7);
+        client.bind().unwrap();
+        client.query(group).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(1, server.update());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(1, client.update());
+        assert_eq!(b"group".to_vec(), client.get_servers()[0].info);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{fmt, io};
+
+use super::{
+    receive_result::{RECEIVE_ERROR_BUFFER_TOO_SMALL, RECEIVE_ERROR_CHANNEL},
+    SEND_ERROR_CHANNEL, SEND_ERROR_FRAGMENT, SEND_ERROR_IDENTITY, SEND_ERROR_LENGTH, SEND_ERROR_MESSAGE_SIZE,
+    SEND_ERROR_SOCKET, SEND_ERROR_UNKNOWN, SEND_ERROR_UNKNOWN_TARGET,
+};
+
+// Errors for the Rust api, the try_ functions.  The ffi and the original functions keep the numeric codes,
+// from_send_error/from_receive_error and code convert between them.
+#[derive(Debug)]
+pub enum TachyonError {
+    // no connection for the address or identity
+    UnknownTarget,
+    // identity is enabled and this side isn't linked yet, or the target identity has no address
+    IdentityNotLinked,
+    UnknownChannel,
+    // empty, or an unreliable message larger then a datagram
+    InvalidLength,
+    // larger then max_message_size, or needs more fragments then the channel allows
+    MessageTooLarge,
+    // send buffers couldn't be had for every fragment of the message
+    WindowFull,
+    // message left for the next receive, needed is it's length
+    BufferTooSmall { needed: usize },
+    // send with no socket, bind or connect first
+    NotConnected,
+    Socket(io::Error),
+    TooManyServers,
+    DuplicateServer,
+    // typed messages, a type that wasn't registered or a type id nothing is registered for
+    UnregisteredType(&'static str),
+    UnknownMessageType(u16),
+    Encode(String),
+    Decode(String),
+    Unknown,
+}
+
+impl TachyonError {
+    // None for 0
+    pub fn from_send_error(error: u32) -> Option<Self> {
+        let error = match error {
+            0 => return None,
+            SEND_ERROR_SOCKET => TachyonError::NotConnected,
+            SEND_ERROR_CHANNEL => TachyonError::UnknownChannel,
+            SEND_ERROR_FRAGMENT => TachyonError::WindowFull,
+            SEND_ERROR_LENGTH => TachyonError::InvalidLength,
+            SEND_ERROR_IDENTITY => TachyonError::IdentityNotLinked,
+            SEND_ERROR_MESSAGE_SIZE => TachyonError::MessageTooLarge,
+            SEND_ERROR_UNKNOWN_TARGET => TachyonError::UnknownTarget,
+            _ => TachyonError::Unknown,
+        };
+        return Some(error);
+    }
+
+    // None for 0, length is the receive result length
+    pub fn from_receive_error(error: u32, length: u32) -> Option<Self> {
+        let error = match error {
+            0 => return None,
+            RECEIVE_ERROR_CHANNEL => TachyonError::UnknownChannel,
+            RECEIVE_ERROR_BUFFER_TOO_SMALL => TachyonError::BufferTooSmall { needed: length as usize },
+            _ => TachyonError::Unknown,
+        };
+        return Some(error);
+    }
+
+    // numeric code for the ffi, send codes except for BufferTooSmall
+    pub fn code(&self) -> u32 {
+        match self {
+            TachyonError::UnknownTarget => SEND_ERROR_UNKNOWN_TARGET,
+            TachyonError::IdentityNotLinked => SEND_ERROR_IDENTITY,
+            TachyonError::UnknownChannel => SEND_ERROR_CHANNEL,
+            TachyonError::InvalidLength => SEND_ERROR_LENGTH,
+            TachyonError::MessageTooLarge => SEND_ERROR_MESSAGE_SIZE,
+            TachyonError::WindowFull => SEND_ERROR_FRAGMENT,
+            TachyonError::BufferTooSmall { .. } => RECEIVE_ERROR_BUFFER_TOO_SMALL,
+            TachyonError::NotConnected => SEND_ERROR_SOCKET,
+            TachyonError::Socket(_) => SEND_ERROR_SOCKET,
+            TachyonError::TooManyServers => SEND_ERROR_UNKNOWN,
+            TachyonError::DuplicateServer => SEND_ERROR_UNKNOWN,
+            TachyonError::UnregisteredType(_) => SEND_ERROR_UNKNOWN,
+            TachyonError::UnknownMessageType(_) => SEND_ERROR_UNKNOWN,
+            TachyonError::Encode(_) => SEND_ERROR_UNKNOWN,
+            TachyonError::Decode(_) => SEND_ERROR_UNKNOWN,
+            TachyonError::Unknown => SEND_ERROR_UNKNOWN,
+        }
+    }
+}
+
+impl fmt::Display for TachyonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TachyonError::UnknownTarget => write!(f, "no connection for the target"),
+            TachyonError::IdentityNotLinked => write!(f, "identity not linked"),
+            TachyonError::UnknownChannel => write!(f, "channel not configured"),
+            TachyonError::InvalidLength => write!(f, "invalid message length"),
+            TachyonError::MessageTooLarge => write!(f, "message too large"),
+            TachyonError::WindowFull => write!(f, "send window full"),
+            TachyonError::BufferTooSmall { needed } => write!(f, "receive buffer too small, {} bytes needed", needed),
+            TachyonError::NotConnected => write!(f, "socket not bound or connected"),
+            TachyonError::Socket(error) => write!(f, "socket error: {}", error),
+            TachyonError::TooManyServers => write!(f, "pool has max servers"),
+            TachyonError::DuplicateServer => write!(f, "server id already in the pool"),
+            TachyonError::UnregisteredType(name) => write!(f, "message type {} not registered", name),
+            TachyonError::UnknownMessageType(type_id) => write!(f, "no message type registered for id {}", type_id),
+            TachyonError::Encode(error) => write!(f, "encode failed: {}", error),
+            TachyonError::Decode(error) => write!(f, "decode failed: {}", error),
+            TachyonError::Unknown => write!(f, "unknown error"),
+        }
+    }
+}
+
+impl std::error::Error for TachyonError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TachyonError::Socket(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TachyonError {
+    fn from(error: io::Error) -> Self {
+        return TachyonError::Socket(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        for code in 1..9 {
+            let error = TachyonError::from_send_error(code).unwrap();
+            assert_eq!(code, error.code());
+        }
+        assert!(TachyonError::from_send_error(0).is_none());
+
+        match TachyonError::from_receive_error(RECEIVE_ERROR_BUFFER_TOO_SMALL, 500) {
+            Some(TachyonError::BufferTooSmall { needed }) => assert_eq!(500, needed),
+            _ => panic!("expected BufferTooSmall"),
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use super::network_address::NetworkAddress;
+
+// queued events past this drop the oldest, so a Tachyon that's never polled doesn't grow without bound.  Messages
+// received by poll_events are returned in the same call and not counted.
+pub const MAX_QUEUED_EVENTS: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisconnectReason {
+    // the client sent an unlink
+    Unlinked,
+    // the identity linked again, from this or another address
+    Relinked,
+    // set_identity changed or removed the identity
+    IdentityChanged,
+}
+
+// Everything a poll can produce, for Rust code that would rather match on events then register ffi callbacks.
+// identity is the connection's identity id, 0 when identities are not used.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Message { channel: u8, address: NetworkAddress, identity: u32, data: Vec<u8> },
+    Connected { address: NetworkAddress, identity: u32 },
+    Disconnected { address: NetworkAddress, identity: u32, reason: DisconnectReason },
+    // on the server an identity linked to the address, on the client our own identity was linked
+    IdentityLinked { address: NetworkAddress, identity: u32, session_id: u32 },
+    IdentityUnlinked { address: NetworkAddress, identity: u32, session_id: u32 },
+    // A partially received fragmented message was dropped, it expired or went over max_fragment_bytes.  The sender
+    // saw it acked, so this is the only place the loss shows up.
+    MessageLost { channel: u8, address: NetworkAddress, identity: u32 },
+}
@@ -1,7 +1,9 @@
 
+use std::time::Duration;
+
 use crate::tachyon::*;
 
-use super::pool::SendTarget;
+use super::{pool::SendTarget, delivery::{DeliveryEvent, DeliveryEventCallback}, topic::PublishResult};
 
 
 #[no_mangle]
@@ -18,6 +20,12 @@ pub extern "C" fn register_callbacks(tachyon_ptr: *mut Tachyon, identity_event_c
     }
 }
 
+#[no_mangle]
+pub extern "C" fn register_delivery_callback(tachyon_ptr: *mut Tachyon, delivery_event_callback: Option<DeliveryEventCallback>) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.delivery_event_callback = delivery_event_callback;
+}
+
 #[no_mangle]
 pub extern "C" fn create_tachyon(config_ptr: *const TachyonConfig) -> *mut Tachyon {
     let config: TachyonConfig = unsafe { std::ptr::read(config_ptr as *const _) };
@@ -68,6 +76,34 @@ pub extern "C" fn configure_channel(tachyon_ptr: *mut Tachyon, channel_id: u8, c
     }
 }
 
+#[no_mangle]
+pub extern "C" fn remove_channel(tachyon_ptr: *mut Tachyon, channel_id: u8) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if tachyon.remove_channel(channel_id) {
+        return 1;
+    } else {
+        return -1;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn set_compression_dictionary(tachyon_ptr: *mut Tachyon, channel_id: u8, dictionary: *const u8, length: i32) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    tachyon.set_compression_dictionary(channel_id, dictionary_slice(dictionary, length));
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_take_channel_mismatch(tachyon_ptr: *mut Tachyon, mismatch: *mut ChannelMismatch) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if let Some(channel_mismatch) = tachyon.take_channel_mismatch() {
+        unsafe {
+            (*mismatch) = channel_mismatch;
+        }
+        return 1;
+    }
+    return 0;
+}
+
 pub fn copy_send_result(from: TachyonSendResult, to: *mut TachyonSendResult) {
     unsafe {
         (*to).sent_len = from.sent_len;
@@ -86,6 +122,79 @@ pub extern "C" fn send_to_target(tachyon_ptr: *mut Tachyon, channel: u8, target_
     copy_send_result(result, ret);
 }
 
+#[no_mangle]
+pub extern "C" fn tachyon_subscribe(tachyon_ptr: *mut Tachyon, topic: u32, target_ptr: *const SendTarget) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    return tachyon.subscribe(topic, target) as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_unsubscribe(tachyon_ptr: *mut Tachyon, topic: u32, target_ptr: *const SendTarget) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    return tachyon.unsubscribe(topic, target) as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_publish(tachyon_ptr: *mut Tachyon, topic: u32, channel: u8, data: *mut u8, length: i32, ret: *mut PublishResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+
+    let result = tachyon.publish(topic, channel, slice, length as usize);
+    unsafe {
+        *ret = result;
+    }
+}
+
+// exclude list for the broadcast functions, may be null when count is 0
+// null or a length of 0 or less is an empty dictionary, which clears it
+pub fn dictionary_slice<'a>(dictionary: *const u8, length: i32) -> &'a [u8] {
+    if dictionary.is_null() || length <= 0 {
+        return &[];
+    }
+    return unsafe { std::slice::from_raw_parts(dictionary, length as usize) };
+}
+
+pub fn exclude_slice<'a>(exclude_ptr: *const SendTarget, exclude_count: i32) -> &'a [SendTarget] {
+    if exclude_ptr.is_null() || exclude_count <= 0 {
+        return &[];
+    }
+    return unsafe { std::slice::from_raw_parts(exclude_ptr, exclude_count as usize) };
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_broadcast(tachyon_ptr: *mut Tachyon, channel: u8, data: *mut u8, length: i32, exclude_ptr: *const SendTarget, exclude_count: i32, ret: *mut PublishResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+    let exclude = exclude_slice(exclude_ptr, exclude_count);
+
+    let result = tachyon.broadcast(channel, slice, length as usize, exclude);
+    unsafe {
+        *ret = result;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn send_unreliable_to_target(tachyon_ptr: *mut Tachyon, channel: u8, target_ptr: *const SendTarget, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+
+    let result = tachyon.send_unreliable_to_target(channel, target, slice, length as usize);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn send_reliable_ttl(tachyon_ptr: *mut Tachyon, channel: u8, naddress: *const NetworkAddress, data: *mut u8, length: i32, ttl: u32, ret: *mut TachyonSendResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+
+    let result = tachyon.send_reliable_ttl(channel, address, slice, length as usize, ttl);
+    copy_send_result(result, ret);
+}
+
 #[no_mangle]
 pub extern "C" fn receive(tachyon_ptr: *mut Tachyon, data: *mut u8, receive_buffer_len: u32, ret: *mut TachyonReceiveResult) {
     let tachyon = unsafe { &mut *tachyon_ptr };
@@ -100,6 +209,51 @@ pub extern "C" fn receive(tachyon_ptr: *mut Tachyon, data: *mut u8, receive_buff
     }
 }
 
+// receive that blocks up to timeout_ms for a message
+#[no_mangle]
+pub extern "C" fn receive_timeout(tachyon_ptr: *mut Tachyon, data: *mut u8, receive_buffer_len: u32, timeout_ms: u32, ret: *mut TachyonReceiveResult) {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, receive_buffer_len as usize) };
+    let result = tachyon.receive_timeout(slice, Duration::from_millis(timeout_ms as u64));
+
+    unsafe {
+        (*ret).channel = result.channel;
+        (*ret).address = result.address;
+        (*ret).length = result.length;
+        (*ret).error = result.error;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_is_delivered(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, channel_id: u8, sequence: u16) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    if tachyon.is_delivered(address, channel_id, sequence) {
+        return 1;
+    } else {
+        return 0;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_take_delivery_event(tachyon_ptr: *mut Tachyon, event: *mut DeliveryEvent) -> i32 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    if let Some(delivery_event) = tachyon.take_delivery_event() {
+        unsafe {
+            (*event) = delivery_event;
+        }
+        return 1;
+    }
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn tachyon_get_rtt(tachyon_ptr: *mut Tachyon, naddress: *const NetworkAddress, channel_id: u8) -> u64 {
+    let tachyon = unsafe { &mut *tachyon_ptr };
+    let address: NetworkAddress = unsafe { std::ptr::read(naddress as *const _) };
+    return tachyon.get_rtt_micros(address, channel_id);
+}
+
 #[no_mangle]
 pub extern "C" fn tachyon_update(tachyon_ptr: *mut Tachyon) {
     let tachyon = unsafe { &mut *tachyon_ptr };
@@ -1,5 +1,7 @@
+use std::collections::VecDeque;
 use std::time::Instant;
 
+use super::byte_buffer_pool::{ByteBuffer, ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT};
 use super::header::*;
 use super::send_buffer_manager::*;
 use super::sequence::*;
@@ -7,40 +9,259 @@ use rustc_hash::FxHashMap;
 
 const GROUP_EXPIRE: u128 = 5000;
 const FRAG_SIZE: usize = 1200;
+// fragments leave this much of the max datagram size for headers
+const FRAGMENT_HEADROOM: usize = BYTE_BUFFER_SIZE_DEFAULT - FRAG_SIZE;
+// larger messages get an unpooled buffer
+const REASSEMBLY_BUFFER_SIZE: usize = 64 * 1024;
+const REASSEMBLY_POOL_SIZE: usize = 4;
+
+// Fragments are written straight into one buffer at index * body size, body size being the length of any
+// fragment but the last.  If the last fragment arrives first it's held until we know where it goes.
+pub struct FragmentGroup {
+    pub buffer: Option<ByteBuffer>,
+    pub body_size: usize,
+    pub start_sequence: u16,
+    pub fragment_count: u16,
+    pub received_fragments: Vec<bool>,
+    pub received_count: u16,
+    pub last_fragment: Option<Vec<u8>>,
+    // total body length, known once the last fragment is in
+    pub length: usize,
+    pub bytes: usize,
+    pub received_at: Instant,
+    pub last_received_at: Instant,
+}
+
+impl FragmentGroup {
+    pub fn create(received_at: Instant, start_sequence: u16, fragment_count: u16) -> Self {
+        let group = FragmentGroup {
+            buffer: None,
+            body_size: 0,
+            start_sequence,
+            fragment_count,
+            received_fragments: vec![false; fragment_count as usize],
+            received_count: 0,
+            last_fragment: None,
+            length: 0,
+            bytes: 0,
+            received_at,
+            last_received_at: received_at,
+        };
+        return group;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        return self.received_count == self.fragment_count;
+    }
+
+    // false if the fragment doesn't fit the group, or the group would be larger then max_length
+    fn write(&mut self, index: usize, body: &[u8], max_length: usize, buffer_pool: &mut ByteBufferPool) -> bool {
+        let last_index = self.fragment_count as usize - 1;
+
+        if self.buffer.is_none() {
+            if index == last_index && self.fragment_count > 1 {
+                if body.len() > max_length {
+                    return false;
+                }
+                self.last_fragment = Some(body.to_vec());
+                return true;
+            }
+            // the last fragment adds at least a byte
+            if body.len() * last_index >= max_length {
+                return false;
+            }
+            self.body_size = body.len();
+            self.buffer = Some(buffer_pool.get_buffer(self.body_size * self.fragment_count as usize));
+
+            if let Some(last_fragment) = self.last_fragment.take() {
+                if !self.copy_body(last_index, &last_fragment) {
+                    return false;
+                }
+            }
+        }
+
+        return self.copy_body(index, body);
+    }
+
+    fn copy_body(&mut self, index: usize, body: &[u8]) -> bool {
+        let last_index = self.fragment_count as usize - 1;
+        if index < last_index && body.len() != self.body_size {
+            return false;
+        }
+        if index == last_index && body.len() > self.body_size {
+            return false;
+        }
+
+        let offset = index * self.body_size;
+        if let Some(buffer) = &mut self.buffer {
+            buffer.get_mut()[offset..offset + body.len()].copy_from_slice(body);
+        }
+        if index == last_index {
+            self.length = offset + body.len();
+        }
+        return true;
+    }
+}
+
 pub struct Fragmentation {
+    pub fragment_size: usize,
     pub next_group: u16,
-    pub received: FxHashMap<u16, FxHashMap<u16, Vec<u8>>>,
-    pub received_at: FxHashMap<u16, Instant>,
+    pub received: FxHashMap<u16, FragmentGroup>,
+    // (group, started at, expire from) in roughly time order, so expiring only ever looks at the front.
+    // Groups still receiving fragments go to the back again, entries for groups already assembled or reused
+    // stay until they reach the front and are skipped there.
+    pub expire_queue: VecDeque<(u16, Instant, Instant)>,
+    pub received_bytes: usize,
+    pub groups_expired: u64,
+    pub groups_evicted: u64,
+    // expired and evicted groups already returned by take_lost
+    lost_reported: u64,
+    // (start sequence, fragment count) of groups expired or evicted, for the receiver to report as skipped
+    pub dropped: Vec<(u16, u16)>,
+    // fragments from the other side are checked against these before anything is allocated
+    pub max_fragments: usize,
+    pub max_message_size: usize,
+    pub buffer_pool: ByteBufferPool,
 }
 
 impl Fragmentation {
     pub fn default() -> Self {
-        let default = Fragmentation {
+        return Fragmentation::create(FRAG_SIZE);
+    }
+
+    pub fn create(fragment_size: usize) -> Self {
+        let fragmentation = Fragmentation {
+            fragment_size,
             next_group: 1,
             received: FxHashMap::default(),
-            received_at: FxHashMap::default(),
+            expire_queue: VecDeque::new(),
+            received_bytes: 0,
+            groups_expired: 0,
+            groups_evicted: 0,
+            lost_reported: 0,
+            dropped: Vec::new(),
+            max_fragments: u16::MAX as usize,
+            max_message_size: usize::MAX,
+            buffer_pool: ByteBufferPool::create(REASSEMBLY_BUFFER_SIZE, REASSEMBLY_POOL_SIZE),
         };
-        return default;
+        return fragmentation;
+    }
+
+    pub fn fragment_size_for(max_datagram_size: usize) -> usize {
+        return max_datagram_size - FRAGMENT_HEADROOM;
     }
 
     pub fn expire_groups(&mut self) {
-        let mut expired: Vec<u16> = Vec::new();
-        for (group, time) in &self.received_at {
-            if time.elapsed().as_millis() > GROUP_EXPIRE {
-                self.received.remove(group);
-                expired.push(*group);
+        let now = Instant::now();
+        while let Some((group, received_at, expire_from)) = self.expire_queue.front() {
+            if now.duration_since(*expire_from).as_millis() <= GROUP_EXPIRE {
+                break;
+            }
+            let group = *group;
+            let received_at = *received_at;
+            self.expire_queue.pop_front();
+
+            if let Some(fragment_group) = self.received.get(&group) {
+                if fragment_group.received_at == received_at && now.duration_since(fragment_group.last_received_at).as_millis() <= GROUP_EXPIRE {
+                    self.expire_queue.push_back((group, received_at, fragment_group.last_received_at));
+                    continue;
+                }
+            }
+
+            if self.remove_group(group, received_at) {
+                self.groups_expired += 1;
+            }
+        }
+    }
+
+    // drops the oldest partially received group, false if there are none
+    pub fn evict_oldest(&mut self) -> bool {
+        while let Some((group, received_at, _)) = self.expire_queue.pop_front() {
+            if self.remove_group(group, received_at) {
+                self.groups_evicted += 1;
+                return true;
             }
         }
-        for group in expired {
-            self.received_at.remove(&group);
+        return false;
+    }
+
+    // Groups expired or evicted since the last call.  Their fragments were already received, so each one is a
+    // reliable message that is lost.
+    pub fn take_lost(&mut self) -> u64 {
+        let lost = self.groups_expired + self.groups_evicted;
+        let count = lost - self.lost_reported;
+        self.lost_reported = lost;
+        return count;
+    }
+
+    // Start sequence of the oldest group still waiting on fragments, acks don't go past it until the group is
+    // complete.  Groups missing a fragment the receiver already skipped can't complete and are left out.
+    pub fn oldest_incomplete_sequence(&self, current_sequence: u16) -> Option<u16> {
+        let mut oldest: Option<u16> = None;
+        for group in self.received.values() {
+            if group.is_complete() {
+                continue;
+            }
+
+            let mut sequence = group.start_sequence;
+            for received in &group.received_fragments {
+                if !received {
+                    break;
+                }
+                sequence = Sequence::next_sequence(sequence);
+            }
+            if Sequence::is_equal_to_or_less_than(sequence, current_sequence) {
+                continue;
+            }
+
+            let is_oldest = match oldest {
+                Some(start) => Sequence::is_greater_then(start, group.start_sequence),
+                None => true,
+            };
+            if is_oldest {
+                oldest = Some(group.start_sequence);
+            }
+        }
+        return oldest;
+    }
+
+    pub fn oldest_received_at(&self) -> Option<Instant> {
+        for (group, received_at, _) in &self.expire_queue {
+            if self.is_current_group(*group, *received_at) {
+                return Some(*received_at);
+            }
+        }
+        return None;
+    }
+
+    // group ids wrap, so queue entries are matched on when the group was started as well
+    fn is_current_group(&self, group: u16, received_at: Instant) -> bool {
+        match self.received.get(&group) {
+            Some(fragment_group) => {
+                return fragment_group.received_at == received_at;
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    fn remove_group(&mut self, group: u16, received_at: Instant) -> bool {
+        if !self.is_current_group(group, received_at) {
+            return false;
         }
+        if let Some(fragment_group) = self.received.get(&group) {
+            self.dropped.push((fragment_group.start_sequence, fragment_group.fragment_count));
+        }
+        self.take_group(group);
+        return true;
     }
 
-    pub fn should_fragment(length: usize) -> bool {
-        return length >= FRAG_SIZE;
+    pub fn should_fragment(&self, length: usize) -> bool {
+        return length >= self.fragment_size;
     }
 
-    fn get_next_group(&mut self) -> u16 {
+    pub fn get_next_group(&mut self) -> u16 {
         self.next_group += 1;
         if self.next_group >= std::u16::MAX - 1 {
             self.next_group = 1;
@@ -48,103 +269,112 @@ impl Fragmentation {
         return self.next_group;
     }
 
-    fn get_group_length(map: &FxHashMap<u16, Vec<u8>>) -> usize {
-        let mut length = 0;
-        for (_, value) in map {
-            length += value.len() - TACHYON_FRAGMENTED_HEADER_SIZE;
+    fn take_group(&mut self, group: u16) -> Option<ByteBuffer> {
+        if let Some(fragment_group) = self.received.remove(&group) {
+            self.received_bytes -= fragment_group.bytes;
+            if let Some(mut buffer) = fragment_group.buffer {
+                buffer.length = fragment_group.length;
+                return Some(buffer);
+            }
         }
-        return length;
+        return None;
     }
 
-    pub fn assemble(&mut self, header: Header) -> Result<Vec<u8>, ()> {
-        let map = match self.received.get_mut(&header.fragment_group) {
-            Some(v) => v,
+    // the whole message as buffer.get()[0..buffer.length], hand the buffer back with return_buffer when done
+    pub fn assemble(&mut self, header: Header) -> Result<ByteBuffer, ()> {
+        match self.received.get(&header.fragment_group) {
+            Some(group) => {
+                if !group.is_complete() {
+                    return Err(());
+                }
+            }
             None => {
                 return Err(());
             }
-        };
-        if map.len() != header.fragment_count as usize {
-            return Err(());
         }
 
-        let body_length = Fragmentation::get_group_length(map);
-
-        let mut buffer: Vec<u8> = vec![0; body_length];
-        let mut offset = 0;
-
-        let mut seq = header.fragment_start_sequence;
-        for _ in 0..header.fragment_count {
-            match map.get_mut(&seq) {
-                Some(fragment) => {
-                    let frag_body_len = fragment.len() - TACHYON_FRAGMENTED_HEADER_SIZE;
-                    let src = &fragment[TACHYON_FRAGMENTED_HEADER_SIZE..fragment.len()];
-                    let dest = &mut buffer[offset..offset + frag_body_len];
-                    dest.copy_from_slice(src);
+        match self.take_group(header.fragment_group) {
+            Some(buffer) => {
+                return Ok(buffer);
+            }
+            None => {
+                return Err(());
+            }
+        }
+    }
 
-                    offset += frag_body_len;
-                }
-                None => {
-                    self.received.remove(&header.fragment_group);
-                    return Err(());
-                }
+    // length of the message once all fragments are in
+    pub fn get_assembled_length(&self, group: u16) -> Option<usize> {
+        if let Some(fragment_group) = self.received.get(&group) {
+            if fragment_group.is_complete() {
+                return Some(fragment_group.length);
             }
-            seq = Sequence::next_sequence(seq);
         }
+        return None;
+    }
 
-        self.received.remove(&header.fragment_group);
-        return Ok(buffer);
+    pub fn return_buffer(&mut self, buffer: ByteBuffer) {
+        self.buffer_pool.return_buffer(buffer);
     }
 
     pub fn receive_fragment(&mut self, data: &[u8], length: usize) -> (bool, bool) {
         let header = Header::read_fragmented(data);
+        if header.fragment_count == 0 || header.fragment_count as usize > self.max_fragments || length < TACHYON_FRAGMENTED_HEADER_SIZE {
+            return (false, false);
+        }
+
+        let index = Sequence::distance(header.fragment_start_sequence, header.sequence) as usize;
+        if index >= header.fragment_count as usize {
+            return (false, false);
+        }
+
         if !self.received.contains_key(&header.fragment_group) {
-            self.received.insert(header.fragment_group, FxHashMap::default());
-            self.received_at.insert(header.fragment_group, Instant::now());
+            let now = Instant::now();
+            self.received.insert(header.fragment_group, FragmentGroup::create(now, header.fragment_start_sequence, header.fragment_count));
+            self.expire_queue.push_back((header.fragment_group, now, now));
         }
-        if let Some(map) = self.received.get_mut(&header.fragment_group) {
-            let slice = &data[0..length as usize];
+        if let Some(group) = self.received.get_mut(&header.fragment_group) {
+            if group.start_sequence != header.fragment_start_sequence || group.fragment_count != header.fragment_count {
+                return (false, false);
+            }
 
-            let mut fragment: Vec<u8> = vec![0; length];
-            fragment[..].copy_from_slice(slice);
-            if !map.contains_key(&header.sequence) {
-                map.insert(header.sequence, fragment);
+            if !group.received_fragments[index] {
+                let body = &data[TACHYON_FRAGMENTED_HEADER_SIZE..length];
+                if !group.write(index, body, self.max_message_size, &mut self.buffer_pool) {
+                    return (false, false);
+                }
+                group.received_fragments[index] = true;
+                group.received_count += 1;
+                group.bytes += length;
+                group.last_received_at = Instant::now();
+                self.received_bytes += length;
             }
 
-            return (true, map.len() == header.fragment_count as usize);
+            return (true, group.is_complete());
         }
 
         return (false, false);
     }
 
+    pub fn get_fragment_count(&self, length: usize) -> usize {
+        return (length + self.fragment_size - 1) / self.fragment_size;
+    }
+
     pub fn create_fragments(&mut self, sender: &mut SendBufferManager, channel: u8, data: &[u8], length: usize) -> Vec<u16> {
         let slice = &data[0..length];
 
-        let chunks = slice.chunks(FRAG_SIZE as usize);
+        let chunks = slice.chunks(self.fragment_size);
         let fragment_count = chunks.len() as u16;
         let mut fragments: Vec<u16> = Vec::new();
         let group = self.get_next_group();
 
-        let mut start_sequence = 0;
-        let mut index = 0;
+        // send buffers hand out sequences in order
+        let start_sequence = Sequence::next_sequence(sender.current_sequence);
 
         for chunk in chunks {
-            let chunk_len = chunk.len();
-            let fragment_len = chunk_len + TACHYON_FRAGMENTED_HEADER_SIZE;
-
-            match sender.create_send_buffer(fragment_len) {
-                Some(send_buffer) => {
-                    let sequence = send_buffer.sequence;
-                    if index == 0 {
-                        start_sequence = sequence;
-                    }
-
-                    let fragment_header = Header::create_fragmented(sequence, channel, group, start_sequence, fragment_count);
-                    fragment_header.write_fragmented(&mut send_buffer.byte_buffer.get_mut());
-
-                    send_buffer.byte_buffer.get_mut()[TACHYON_FRAGMENTED_HEADER_SIZE..chunk_len + TACHYON_FRAGMENTED_HEADER_SIZE].copy_from_slice(chunk);
+            match Fragmentation::create_fragment(sender, channel, group, start_sequence, fragment_count, chunk) {
+                Some(sequence) => {
                     fragments.push(sequence);
-
-                    index += 1;
                 }
                 None => {
                     fragments.clear();
@@ -155,6 +385,26 @@ impl Fragmentation {
 
         return fragments;
     }
+
+    // single fragment of a group into the next send buffer
+    pub fn create_fragment(sender: &mut SendBufferManager, channel: u8, group: u16, start_sequence: u16, fragment_count: u16, chunk: &[u8]) -> Option<u16> {
+        let chunk_len = chunk.len();
+        let fragment_len = chunk_len + TACHYON_FRAGMENTED_HEADER_SIZE;
+
+        match sender.create_send_buffer(fragment_len) {
+            Some(send_buffer) => {
+                let sequence = send_buffer.sequence;
+                let fragment_header = Header::create_fragmented(sequence, channel, group, start_sequence, fragment_count);
+                fragment_header.write_fragmented(&mut send_buffer.byte_buffer.get_mut());
+
+                send_buffer.byte_buffer.get_mut()[TACHYON_FRAGMENTED_HEADER_SIZE..fragment_len].copy_from_slice(chunk);
+                return Some(sequence);
+            }
+            None => {
+                return None;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -166,16 +416,81 @@ mod tests {
     #[test]
     fn test_expire() {
         let mut frag = Fragmentation::default();
-        frag.received_at.insert(1, Instant::now());
-        frag.expire_groups();
-        assert!(frag.received_at.contains_key(&1));
-        assert_eq!(1, frag.received_at.len());
+        let expired_at = Instant::now() - Duration::new(6, 0);
+        frag.received.insert(2, FragmentGroup::create(expired_at, 1, 2));
+        frag.expire_queue.push_back((2, expired_at, expired_at));
+
+        let now = Instant::now();
+        frag.received.insert(1, FragmentGroup::create(now, 1, 2));
+        frag.expire_queue.push_back((1, now, now));
 
-        let now = Instant::now() - Duration::new(6, 0);
-        frag.received_at.insert(2, now);
         frag.expire_groups();
-        assert!(!frag.received_at.contains_key(&2));
-        assert_eq!(1, frag.received_at.len());
+        assert!(frag.received.contains_key(&1));
+        assert!(!frag.received.contains_key(&2));
+        assert_eq!(1, frag.expire_queue.len());
+        assert_eq!(1, frag.groups_expired);
+        assert_eq!(vec![(1, 2)], frag.dropped);
+
+        // reused group id is not expired by the stale entry
+        frag.received.insert(3, FragmentGroup::create(now, 1, 2));
+        frag.expire_queue.push_front((3, expired_at, expired_at));
+        frag.expire_groups();
+        assert!(frag.received.contains_key(&3));
+        assert_eq!(1, frag.groups_expired);
+
+        // started long ago but still receiving fragments
+        let mut group = FragmentGroup::create(expired_at, 1, 2);
+        group.last_received_at = now;
+        frag.received.insert(4, group);
+        frag.expire_queue.push_front((4, expired_at, expired_at));
+        frag.expire_groups();
+        assert!(frag.received.contains_key(&4));
+        assert_eq!(1, frag.groups_expired);
+        assert_eq!(now, frag.expire_queue.back().unwrap().2);
+    }
+
+    #[test]
+    fn test_evict_oldest() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+
+        let data: Vec<u8> = vec![3; 2500];
+        for _ in 0..2 {
+            let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+            let send_buffer = sender.get_send_buffer(created[0]).unwrap();
+            frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+        }
+        assert_eq!(2, frag.received.len());
+        assert_eq!(2 * (1200 + TACHYON_FRAGMENTED_HEADER_SIZE), frag.received_bytes);
+
+        assert!(frag.evict_oldest());
+        assert_eq!(1, frag.received.len());
+        assert!(frag.received.contains_key(&3));
+        assert_eq!(1200 + TACHYON_FRAGMENTED_HEADER_SIZE, frag.received_bytes);
+
+        assert!(frag.evict_oldest());
+        assert!(!frag.evict_oldest());
+        assert_eq!(0, frag.received_bytes);
+        assert_eq!(2, frag.groups_evicted);
+        assert_eq!(vec![(1, 3), (4, 3)], frag.dropped);
+    }
+
+    #[test]
+    fn test_oldest_incomplete() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+
+        let data: Vec<u8> = vec![3; 2500];
+        for _ in 0..2 {
+            let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+            let send_buffer = sender.get_send_buffer(created[0]).unwrap();
+            frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+        }
+        assert_eq!(Some(1), frag.oldest_incomplete_sequence(1));
+
+        // the first group's missing fragment was skipped
+        assert_eq!(Some(4), frag.oldest_incomplete_sequence(2));
+        assert_eq!(None, frag.oldest_incomplete_sequence(5));
     }
 
     #[test]
@@ -204,6 +519,22 @@ mod tests {
         assert_eq!(2, header.sequence);
     }
 
+    #[test]
+    fn test_create_with_fragment_size() {
+        let mut frag = Fragmentation::create(Fragmentation::fragment_size_for(8000));
+        let mut sender = SendBufferManager::create(8000);
+        assert_eq!(7960, frag.fragment_size);
+        assert!(!frag.should_fragment(7959));
+        assert!(frag.should_fragment(7960));
+
+        let data: Vec<u8> = vec![0; 20000];
+        let result = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+        assert_eq!(3, result.len());
+        let buffer = sender.get_send_buffer(result[0]).unwrap();
+        assert_eq!(7960 + TACHYON_FRAGMENTED_HEADER_SIZE, buffer.byte_buffer.length);
+        assert!(buffer.byte_buffer.pooled);
+    }
+
     #[test]
     fn test_receive() {
         let mut frag = Fragmentation::default();
@@ -227,10 +558,57 @@ mod tests {
         let assembled = frag.assemble(header);
         assert!(assembled.is_ok());
         let assembled_data = assembled.unwrap();
-        assert_eq!(2500, assembled_data.len());
+        assert_eq!(2500, assembled_data.length);
+        assert!(assembled_data.pooled);
+        assert_eq!(0, frag.received_bytes);
 
-        for i in 0..assembled_data.len() {
+        for i in 0..assembled_data.length {
             assert_eq!(3, assembled_data[i]);
         }
     }
+
+    #[test]
+    fn test_receive_limits() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+        let data: Vec<u8> = vec![3; 2500];
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+
+        frag.max_fragments = 2;
+        let send_buffer = sender.get_send_buffer(created[0]).unwrap();
+        assert_eq!((false, false), frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length));
+        assert_eq!(0, frag.received.len());
+
+        frag.max_fragments = 3;
+        frag.max_message_size = 2400;
+        assert_eq!((false, false), frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length));
+        assert!(frag.received.values().all(|group| group.buffer.is_none()));
+        assert_eq!(0, frag.received_bytes);
+
+        frag.max_message_size = 2500;
+        assert_eq!((true, false), frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length));
+    }
+
+    #[test]
+    fn test_receive_last_first() {
+        let mut frag = Fragmentation::default();
+        let mut sender = SendBufferManager::default();
+
+        let data: Vec<u8> = (0..2500).map(|i| (i % 251) as u8).collect();
+        let created = frag.create_fragments(&mut sender, 1, &data[..], data.len());
+
+        for index in [2, 0, 2, 1] {
+            let send_buffer = sender.get_send_buffer(created[index]).unwrap();
+            frag.receive_fragment(&send_buffer.byte_buffer.get(), send_buffer.byte_buffer.length);
+        }
+
+        let send_buffer = sender.get_send_buffer(created[0]).unwrap();
+        let header = Header::read_fragmented(&send_buffer.byte_buffer.get());
+        let assembled = frag.assemble(header).unwrap();
+        assert_eq!(2500, assembled.length);
+        assert_eq!(&data[..], &assembled.get()[0..assembled.length]);
+
+        frag.return_buffer(assembled);
+        assert_eq!(1, frag.buffer_pool.len());
+    }
 }
@@ -13,6 +13,22 @@ pub const MESSAGE_TYPE_UNLINK_IDENTITY: u8 = 7;
 pub const MESSAGE_TYPE_IDENTITY_LINKED: u8 = 8;
 pub const MESSAGE_TYPE_IDENTITY_UNLINKED: u8 = 9;
 
+// cumulative ack, the header sequence is the receivers current_sequence
+pub const MESSAGE_TYPE_ACK: u8 = 10;
+
+// connection settings exchanged between client and server
+pub const MESSAGE_TYPE_SETTINGS: u8 = 11;
+
+// path mtu discovery, probes are padded out to the size being tested
+pub const MESSAGE_TYPE_MTU_PROBE: u8 = 12;
+pub const MESSAGE_TYPE_MTU_PROBE_ACK: u8 = 13;
+
+// unreliable message on a channel, regular header with the channel's unreliable sequence
+pub const MESSAGE_TYPE_UNRELIABLE_CHANNEL: u8 = 14;
+
+// reliable message packing several small messages, each prefixed with a u16 length
+pub const MESSAGE_TYPE_BATCH: u8 = 15;
+
 pub const TACHYON_HEADER_SIZE: usize = 4;
 pub const TACHYON_NACKED_HEADER_SIZE: usize = 10;
 pub const TACHYON_FRAGMENTED_HEADER_SIZE: usize = 10;
@@ -47,6 +63,94 @@ impl ConnectionHeader {
     }
 }
 
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct MtuProbeHeader {
+    pub message_type: u8,
+    pub probe_id: u16,
+}
+
+impl MtuProbeHeader {
+    pub fn read(buffer: &[u8]) -> Self {
+        let mut header = MtuProbeHeader::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        header.probe_id = reader.read_u16(buffer);
+
+        return header;
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) -> usize {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.message_type, buffer);
+        writer.write_u16(self.probe_id, buffer);
+        return writer.index;
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default)]
+pub struct SettingsHeader {
+    pub message_type: u8,
+    pub max_datagram_size: u32,
+}
+
+impl SettingsHeader {
+    pub fn read(buffer: &[u8]) -> Self {
+        let mut header = SettingsHeader::default();
+        let mut reader = IntBuffer { index: 0 };
+
+        header.message_type = reader.read_u8(buffer);
+        header.max_datagram_size = reader.read_u32(buffer);
+
+        return header;
+    }
+
+    pub fn write(&self, buffer: &mut [u8]) -> usize {
+        let mut writer = IntBuffer { index: 0 };
+
+        writer.write_u8(self.message_type, buffer);
+        writer.write_u32(self.max_datagram_size, buffer);
+        return writer.index;
+    }
+}
+
+pub const SETTINGS_CHANNEL_SIZE: usize = 4;
+
+// A channel's delivery as described in settings messages, after the header and a u8 count.
+// ordered is only 1 for reliable ordered channels.
+#[derive(Clone, Copy)]
+#[repr(C)]
+#[derive(Default, Debug, PartialEq)]
+pub struct SettingsChannel {
+    pub id: u8,
+    pub ordered: u8,
+    pub mode: u8,
+    pub compression: u8,
+}
+
+impl SettingsChannel {
+    pub fn read(buffer: &[u8], reader: &mut IntBuffer) -> Self {
+        let mut channel = SettingsChannel::default();
+        channel.id = reader.read_u8(buffer);
+        channel.ordered = reader.read_u8(buffer);
+        channel.mode = reader.read_u8(buffer);
+        channel.compression = reader.read_u8(buffer);
+        return channel;
+    }
+
+    pub fn write(&self, buffer: &mut [u8], writer: &mut IntBuffer) {
+        writer.write_u8(self.id, buffer);
+        writer.write_u8(self.ordered, buffer);
+        writer.write_u8(self.mode, buffer);
+        writer.write_u8(self.compression, buffer);
+    }
+}
+
 #[derive(Clone, Copy)]
 #[repr(C)]
 #[derive(Default)]
@@ -84,6 +84,9 @@ pub struct LengthPrefixed {
     pub writer: IntBuffer
 }
 
+// length u32, channel u16, address
+pub const LENGTH_PREFIX_SIZE: usize = 18;
+
 impl LengthPrefixed {
     pub fn default() -> Self {
         return LengthPrefixed {
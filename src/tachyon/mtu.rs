@@ -0,0 +1,142 @@
+use std::time::Instant;
+
+const PROBE_TIMEOUT: u128 = 250;
+const PROBE_ATTEMPTS: u8 = 3;
+// search stops once low/high are this close
+const SEARCH_STEP: usize = 16;
+
+// Binary search for the largest datagram that makes it to the other side.  low is always a size known to work
+// (or the minimum we assume works), high the largest we are allowed to send.  A probe that is acked raises low,
+// one that times out PROBE_ATTEMPTS times lowers high.
+pub struct MtuDiscovery {
+    pub low: usize,
+    pub high: usize,
+    pub probe_id: u16,
+    pub probe_size: usize,
+    pub attempts: u8,
+    pub in_flight: bool,
+    pub complete: bool,
+    sent_at: Instant,
+}
+
+impl MtuDiscovery {
+    pub fn create(low: usize, high: usize) -> Self {
+        let discovery = MtuDiscovery {
+            low: std::cmp::min(low, high),
+            high,
+            probe_id: 0,
+            probe_size: 0,
+            attempts: 0,
+            in_flight: false,
+            complete: false,
+            sent_at: Instant::now(),
+        };
+        return discovery;
+    }
+
+    pub fn mtu(&self) -> usize {
+        return self.low;
+    }
+
+    // size of the probe to send now if any
+    pub fn next_probe(&mut self, now: Instant) -> Option<usize> {
+        if self.complete {
+            return None;
+        }
+
+        if self.in_flight {
+            if now.duration_since(self.sent_at).as_millis() < PROBE_TIMEOUT {
+                return None;
+            }
+
+            self.attempts += 1;
+            if self.attempts < PROBE_ATTEMPTS {
+                self.sent_at = now;
+                return Some(self.probe_size);
+            }
+            self.on_probe_failed();
+        }
+
+        if self.high - self.low < SEARCH_STEP {
+            self.complete = true;
+            return None;
+        }
+
+        self.probe_size = (self.low + self.high + 1) / 2;
+        self.probe_id = self.probe_id.wrapping_add(1);
+        self.attempts = 0;
+        self.in_flight = true;
+        self.sent_at = now;
+        return Some(self.probe_size);
+    }
+
+    pub fn on_probe_ack(&mut self, probe_id: u16) {
+        if !self.in_flight || probe_id != self.probe_id {
+            return;
+        }
+        self.low = self.probe_size;
+        self.in_flight = false;
+    }
+
+    // timed out or the socket refused to send it
+    pub fn on_probe_failed(&mut self) {
+        if !self.in_flight {
+            return;
+        }
+        self.high = self.probe_size - 1;
+        self.in_flight = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mut discovery = MtuDiscovery::create(256, 8000);
+        let mut now = Instant::now();
+
+        // everything up to 1400 gets through
+        for _ in 0..100 {
+            match discovery.next_probe(now) {
+                Some(size) => {
+                    if size <= 1400 {
+                        discovery.on_probe_ack(discovery.probe_id);
+                    }
+                }
+                None => {
+                    if discovery.complete {
+                        break;
+                    }
+                }
+            }
+            now += Duration::from_millis(PROBE_TIMEOUT as u64 + 1);
+        }
+
+        assert!(discovery.complete);
+        assert!(discovery.mtu() <= 1400);
+        assert!(discovery.mtu() > 1400 - SEARCH_STEP);
+    }
+
+    #[test]
+    fn test_retry_before_fail() {
+        let mut discovery = MtuDiscovery::create(256, 2000);
+        let now = Instant::now();
+        let size = discovery.next_probe(now).unwrap();
+        assert_eq!(None, discovery.next_probe(now));
+
+        let later = now + Duration::from_millis(PROBE_TIMEOUT as u64 + 1);
+        assert_eq!(Some(size), discovery.next_probe(later));
+        assert_eq!(2000, discovery.high);
+
+        // stale ack is ignored
+        discovery.on_probe_ack(discovery.probe_id + 1);
+        assert_eq!(256, discovery.low);
+
+        discovery.on_probe_failed();
+        assert_eq!(size - 1, discovery.high);
+    }
+}
@@ -3,7 +3,7 @@ use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
 };
 
-#[derive(Eq, Default, Clone, Copy)]
+#[derive(Eq, Default, Clone, Copy, Debug)]
 #[repr(C)]
 pub struct NetworkAddress {
     pub a: u16,
@@ -1,24 +1,35 @@
 use std::{
     collections::VecDeque,
-    sync::{Arc}
+    sync::{Arc, Mutex}
 };
 
 use crossbeam::queue::ArrayQueue;
 use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use synchronoise::CountdownEvent;
 
-use super::{network_address::NetworkAddress, Tachyon, TachyonConfig, int_buffer::LengthPrefixed, connection::Connection, TachyonSendResult};
+use super::{network_address::NetworkAddress, Tachyon, TachyonConfig, int_buffer::{LengthPrefixed, LENGTH_PREFIX_SIZE}, connection::Connection, channel::ChannelMismatch, TachyonSendResult, delivery::{DeliveryEvent, DeliveryEventCallback}, receive_result::RECEIVE_ERROR_BUFFER_TOO_SMALL,
+    error::TachyonError, event::Event, topic::{PublishResult, Topics}, SEND_ERROR_UNKNOWN_TARGET};
 
 
 #[derive(Clone, Copy)]
 #[repr(C)]
-#[derive(Default)]
+#[derive(Default, PartialEq, Eq, Hash)]
 pub struct SendTarget {
     pub identity_id: u32,
     pub address: NetworkAddress
 }
 
+impl SendTarget {
+    // by identity if it has one, otherwise by address
+    pub fn matches(&self, conn: &Connection) -> bool {
+        if self.identity_id > 0 {
+            return conn.identity.id == self.identity_id;
+        }
+        return conn.address == self.address;
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 #[repr(C)]
 pub struct PoolServerRef {
@@ -51,10 +62,17 @@ pub struct Pool {
     pub servers_in_use: Arc<ArrayQueue<Tachyon>>,
     pub counter: Option<Arc<CountdownEvent>>,
     pub connections_by_identity: FxHashMap<u32, Connection>,
-    pub connections_by_address: FxHashMap<NetworkAddress, Connection>
-    
+    pub connections_by_address: FxHashMap<NetworkAddress, Connection>,
+    pub topics: Topics,
+    // given to servers as they are created
+    pub delivery_event_callback: Option<DeliveryEventCallback>,
+    pub connection_event_handler: Option<SharedEventHandler>,
+    pub identity_event_handler: Option<SharedEventHandler>
 }
 
+// a handler closure shared by all of the pool's servers
+pub type SharedEventHandler = Arc<Mutex<dyn FnMut(u8, Connection) + Send>>;
+
 impl Pool {
     pub fn create(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32) -> Self {
 
@@ -88,32 +106,49 @@ impl Pool {
             servers_in_use: Arc::new(in_use),
             counter: None,
             connections_by_identity: FxHashMap::default(),
-            connections_by_address: FxHashMap::default()
+            connections_by_address: FxHashMap::default(),
+            topics: Topics::create(),
+            delivery_event_callback: None,
+            connection_event_handler: None,
+            identity_event_handler: None
         };
         return pool;
     }
 
     pub fn create_server(&mut self, config: TachyonConfig, address: NetworkAddress, id: u16) -> bool {
+        return self.try_create_server(config, address, id).is_ok();
+    }
 
+    pub fn try_create_server(&mut self, config: TachyonConfig, address: NetworkAddress, id: u16) -> Result<(), TachyonError> {
         if self.servers.len() > self.max_servers.into() {
-            return false;
+            return Err(TachyonError::TooManyServers);
         }
         if self.servers.contains_key(&id) {
-            return false;
+            return Err(TachyonError::DuplicateServer);
         }
 
         let mut tachyon = Tachyon::create(config);
-        match tachyon.bind(address) {
-            true => {
-                tachyon.id = id;
-                self.servers.insert(id, tachyon);
+        tachyon.try_bind(address)?;
+        tachyon.id = id;
+        tachyon.delivery_event_callback = self.delivery_event_callback;
+        if let Some(handler) = &self.connection_event_handler {
+            Pool::install_connection_event_handler(&mut tachyon, handler.clone());
+        }
+        if let Some(handler) = &self.identity_event_handler {
+            Pool::install_identity_event_handler(&mut tachyon, handler.clone());
+        }
+        self.servers.insert(id, tachyon);
+        return Ok(());
+    }
 
-                return true;
-            }
-            false => {
-                return false;
-            }
+    pub fn update_servers(&mut self) {
+        for server in self.servers.values_mut() {
+            server.update();
         }
+        self.build_connection_maps();
+
+        let connections_by_address = &self.connections_by_address;
+        self.topics.retain_addresses(|address| connections_by_address.contains_key(&address));
     }
 
     pub fn set_identity(&mut self, server_id: u16, id: u32, session_id: u32, on_self: u32) {
@@ -175,7 +210,29 @@ impl Pool {
         return self.servers.get_mut(&id);
     }
 
+    fn unknown_target() -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+        result.error = SEND_ERROR_UNKNOWN_TARGET;
+        return result;
+    }
+
+    // send_to_target with errors as TachyonError
+    pub fn try_send_to_target(&mut self, channel_id: u8, target: SendTarget, data: &mut [u8], length: i32) -> Result<TachyonSendResult, TachyonError> {
+        let result = self.send_to_target(channel_id, target, data, length);
+        match TachyonError::from_send_error(result.error) {
+            Some(error) => {
+                return Err(error);
+            }
+            None => {
+                return Ok(result);
+            }
+        }
+    }
+
     pub fn send_to_target(&mut self,channel_id: u8, target: SendTarget, data: &mut [u8], length: i32) -> TachyonSendResult {
+        if target.identity_id == 0 && target.address.is_broadcast() {
+            return self.broadcast(channel_id, data, length, &[]).to_send_result();
+        }
         if target.identity_id > 0 {
             return self.send_to_identity(channel_id,target.identity_id, data, length);
         } else {
@@ -183,6 +240,40 @@ impl Pool {
         }
     }
 
+    // unreliable send on a channel, see Tachyon::send_unreliable_channel
+    pub fn send_unreliable_to_target(&mut self, channel_id: u8, target: SendTarget, data: &mut [u8], length: i32) -> TachyonSendResult {
+        let conn_option: Option<&Connection>;
+        if target.identity_id > 0 {
+            conn_option = self.connections_by_identity.get(&target.identity_id);
+        } else {
+            conn_option = self.connections_by_address.get(&target.address);
+        }
+
+        if let Some(conn) = conn_option {
+            if let Some(server) = self.servers.get_mut(&conn.tachyon_id) {
+                return server.send_unreliable_channel(channel_id, conn.address, data, length as usize);
+            }
+        }
+        return Pool::unknown_target();
+    }
+
+    // reliable send with a ttl for just this message, see Tachyon::send_reliable_ttl
+    pub fn send_reliable_ttl_to_target(&mut self, channel_id: u8, target: SendTarget, data: &mut [u8], length: i32, ttl: u32) -> TachyonSendResult {
+        let conn_option: Option<&Connection>;
+        if target.identity_id > 0 {
+            conn_option = self.connections_by_identity.get(&target.identity_id);
+        } else {
+            conn_option = self.connections_by_address.get(&target.address);
+        }
+
+        if let Some(conn) = conn_option {
+            if let Some(server) = self.servers.get_mut(&conn.tachyon_id) {
+                return server.send_reliable_ttl(channel_id, conn.address, data, length as usize, ttl);
+            }
+        }
+        return Pool::unknown_target();
+    }
+
     fn send_to_identity(&mut self, channel_id: u8, id: u32, data: &mut [u8], length: i32) -> TachyonSendResult {
         if let Some(conn) = self.connections_by_identity.get(&id) {
             if let Some(server) = self.servers.get_mut(&conn.tachyon_id) {
@@ -193,7 +284,7 @@ impl Pool {
                 }
             }
         }
-        return TachyonSendResult::default();
+        return Pool::unknown_target();
     }
 
     fn send_to_address(&mut self,channel_id: u8, address: NetworkAddress, data: &mut [u8], length: i32) -> TachyonSendResult {
@@ -206,7 +297,140 @@ impl Pool {
                 }
             }
         }
-        return TachyonSendResult::default();
+        return Pool::unknown_target();
+    }
+
+    // Pool wide topics, subscribers can be on any server.  Address subscriptions are removed in update_servers
+    // once the connection is gone.
+    pub fn subscribe(&mut self, topic: u32, target: SendTarget) -> bool {
+        return self.topics.subscribe(topic, target);
+    }
+
+    pub fn unsubscribe(&mut self, topic: u32, target: SendTarget) -> bool {
+        return self.topics.unsubscribe(topic, target);
+    }
+
+    pub fn unsubscribe_all(&mut self, target: SendTarget) {
+        self.topics.unsubscribe_all(target);
+    }
+
+    // Tachyon::publish across servers.  Subscribers are grouped by server and each server sends to it's group on
+    // it's own thread, with one copy of the data per server.
+    pub fn publish(&mut self, topic: u32, channel_id: u8, data: &mut [u8], length: i32) -> PublishResult {
+        let mut result = PublishResult::default();
+        let mut addresses_by_server: FxHashMap<u16, Vec<NetworkAddress>> = FxHashMap::default();
+        let mut seen: FxHashSet<NetworkAddress> = FxHashSet::default();
+
+        for target in self.topics.get_subscribers(topic) {
+            let conn_option: Option<&Connection>;
+            if target.identity_id > 0 {
+                conn_option = self.connections_by_identity.get(&target.identity_id);
+            } else {
+                conn_option = self.connections_by_address.get(&target.address);
+            }
+
+            match conn_option {
+                Some(conn) => {
+                    if seen.insert(conn.address) {
+                        addresses_by_server.entry(conn.tachyon_id).or_default().push(conn.address);
+                    }
+                }
+                None => {
+                    result.count(SEND_ERROR_UNKNOWN_TARGET, 0);
+                }
+            }
+        }
+
+        let length = length as usize;
+        let data: &[u8] = &data[0..length];
+        let results: Vec<PublishResult> = self.servers.par_iter_mut().filter_map(|(id, server)| {
+            let addresses = addresses_by_server.get(id)?;
+            let mut server_data = data.to_vec();
+            return Some(server.send_to_addresses(channel_id, addresses, &mut server_data, length));
+        }).collect();
+
+        for server_result in results {
+            result.merge(server_result);
+        }
+        return result;
+    }
+
+    // Tachyon::broadcast on every server, each on it's own thread
+    pub fn broadcast(&mut self, channel_id: u8, data: &mut [u8], length: i32, exclude: &[SendTarget]) -> PublishResult {
+        let length = length as usize;
+        let data: &[u8] = &data[0..length];
+        let results: Vec<PublishResult> = self.servers.par_iter_mut().map(|(_id, server)| {
+            let mut server_data = data.to_vec();
+            return server.broadcast(channel_id, &mut server_data, length, exclude);
+        }).collect();
+
+        let mut result = PublishResult::default();
+        for server_result in results {
+            result.merge(server_result);
+        }
+        return result;
+    }
+
+    // One closure shared by every server in the pool, including ones created later.  Servers receive on their own
+    // threads so calls are serialized with a lock.
+    pub fn set_connection_event_handler<F>(&mut self, handler: F) where F: FnMut(u8, Connection) + Send + 'static {
+        let handler: SharedEventHandler = Arc::new(Mutex::new(handler));
+        for server in self.servers.values_mut() {
+            Pool::install_connection_event_handler(server, handler.clone());
+        }
+        self.connection_event_handler = Some(handler);
+    }
+
+    pub fn set_identity_event_handler<F>(&mut self, handler: F) where F: FnMut(u8, Connection) + Send + 'static {
+        let handler: SharedEventHandler = Arc::new(Mutex::new(handler));
+        for server in self.servers.values_mut() {
+            Pool::install_identity_event_handler(server, handler.clone());
+        }
+        self.identity_event_handler = Some(handler);
+    }
+
+    fn install_connection_event_handler(server: &mut Tachyon, handler: SharedEventHandler) {
+        server.set_connection_event_handler(move |event_id, connection| {
+            if let Ok(mut handler) = handler.lock() {
+                (*handler)(event_id, connection);
+            }
+        });
+    }
+
+    fn install_identity_event_handler(server: &mut Tachyon, handler: SharedEventHandler) {
+        server.set_identity_event_handler(move |event_id, connection| {
+            if let Ok(mut handler) = handler.lock() {
+                (*handler)(event_id, connection);
+            }
+        });
+    }
+
+    // Tachyon::poll_events on every server in turn, on the calling thread
+    pub fn poll_events(&mut self) -> std::vec::IntoIter<Event> {
+        let mut events: Vec<Event> = Vec::new();
+        for server in self.servers.values_mut() {
+            events.extend(server.poll_events());
+        }
+        self.build_connection_maps();
+        return events.into_iter();
+    }
+
+    pub fn take_delivery_event(&mut self) -> Option<DeliveryEvent> {
+        for server in self.servers.values_mut() {
+            if let Some(event) = server.take_delivery_event() {
+                return Some(event);
+            }
+        }
+        return None;
+    }
+
+    pub fn take_channel_mismatch(&mut self) -> Option<ChannelMismatch> {
+        for server in self.servers.values_mut() {
+            if let Some(mismatch) = server.take_channel_mismatch() {
+                return Some(mismatch);
+            }
+        }
+        return None;
     }
 
     pub fn take_published(&mut self) -> Option<Vec<u8>> {
@@ -230,6 +454,10 @@ impl Pool {
     fn receive_server(server: &mut Tachyon, receive_queue: &mut VecDeque<Vec<u8>>, receive_buffer: &mut Vec<u8>) {
         for _ in 0..100000 {
             let res = server.receive_loop(receive_buffer);
+            if res.error == RECEIVE_ERROR_BUFFER_TOO_SMALL {
+                receive_buffer.resize(res.length as usize, 0);
+                continue;
+            }
             if res.length == 0 || res.error > 0 {
                 break;
             } else {
@@ -345,18 +573,45 @@ impl Pool {
     fn receive_server_into_out_buffer(server: &mut Tachyon, out_buffer: &mut OutBuffer, receive_buffer: &mut Vec<u8>) {
         let mut writer = LengthPrefixed::default();
         for _ in 0..100000 {
-            let res = server.receive_loop(receive_buffer);
+            // only receive what still fits in the out buffer, anything larger stays queued for the next receive
+            let remaining = out_buffer.data.len() - writer.writer.index;
+            if remaining <= LENGTH_PREFIX_SIZE {
+                break;
+            }
+            let max_len = std::cmp::min(receive_buffer.len(), remaining - LENGTH_PREFIX_SIZE);
+
+            let res = server.receive_loop(&mut receive_buffer[0..max_len]);
+            if res.error == RECEIVE_ERROR_BUFFER_TOO_SMALL {
+                let required = res.length as usize;
+                if required > receive_buffer.len() {
+                    receive_buffer.resize(required, 0);
+                }
+                // larger then a whole out buffer
+                if out_buffer.count == 0 && required + LENGTH_PREFIX_SIZE > out_buffer.data.len() {
+                    out_buffer.data.resize(required + LENGTH_PREFIX_SIZE, 0);
+                }
+                if out_buffer.count == 0 {
+                    continue;
+                }
+                break;
+            }
             if res.length == 0 || res.error > 0 {
-                out_buffer.bytes_written = writer.writer.index as u32;
                 break;
             } else {
                 writer.write(res.channel,res.address,&receive_buffer[0..res.length as usize], &mut out_buffer.data);
                 out_buffer.count += 1;
             }
         }
+        out_buffer.bytes_written = writer.writer.index as u32;
     }
 
+    // count is 0 when receive_buffer is too small, use get_next_out_buffer_checked to tell that apart from nothing received
     pub fn get_next_out_buffer(&mut self, receive_buffer: &mut [u8]) -> OutBufferCounts {
+        return self.get_next_out_buffer_checked(receive_buffer).0;
+    }
+
+    // returns counts, error.  RECEIVE_ERROR_BUFFER_TOO_SMALL with bytes_written being the size needed.
+    pub fn get_next_out_buffer_checked(&mut self, receive_buffer: &mut [u8]) -> (OutBufferCounts, u32) {
         let mut result = OutBufferCounts::default();
 
         for _ in 0..self.out_buffers.len() {
@@ -366,6 +621,12 @@ impl Pool {
                     continue;
                 }
 
+                if out_buffer.bytes_written as usize > receive_buffer.len() {
+                    result.bytes_written = out_buffer.bytes_written;
+                    self.out_buffers.push(out_buffer).unwrap_or_default();
+                    return (result, RECEIVE_ERROR_BUFFER_TOO_SMALL);
+                }
+
                 receive_buffer[0..out_buffer.bytes_written as usize].copy_from_slice(&out_buffer.data[0..out_buffer.bytes_written as usize]);
 
                 result.count = out_buffer.count;
@@ -376,10 +637,10 @@ impl Pool {
 
                 self.out_buffers.push(out_buffer).unwrap_or_default();
 
-                return result;
+                return (result, 0);
             }
         }
-        return result;
+        return (result, 0);
     }
 
 }
@@ -391,13 +652,17 @@ mod tests {
     use crate::tachyon::{
         network_address::NetworkAddress,
         tachyon_test::{TachyonTestClient},
-        TachyonConfig, int_buffer::{IntBuffer, LengthPrefixed}
+        TachyonConfig, int_buffer::{IntBuffer, LengthPrefixed, LENGTH_PREFIX_SIZE},
+        receive_result::RECEIVE_ERROR_BUFFER_TOO_SMALL, error::TachyonError, SEND_ERROR_UNKNOWN_TARGET,
+        delivery::DeliveryEvent, pool_ffi::pool_register_delivery_callback
     };
     use std::{
+        sync::{atomic::{AtomicU32, Ordering}, Arc},
         time::Instant,
     };
 
-    use super::Pool;
+    use super::{Pool, SendTarget};
+    use crate::tachyon::pool_unreliable_sender::PoolUnreliableSender;
 
     #[test]
     #[serial]
@@ -489,6 +754,182 @@ mod tests {
 
     }
 
+    #[test]
+    #[serial]
+    fn test_receive_grows_buffers() {
+        let mut pool = Pool::create(4, 64, 1024);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001),1);
+
+        let mut client1 = TachyonTestClient::create(NetworkAddress::localhost(8001));
+        client1.connect();
+        client1.client_send_reliable(1, 32);
+        client1.client_send_reliable(1, 2000);
+
+        pool.receive_blocking_out_buffer();
+
+        // first out buffer only has room for the small message
+        let mut receive_buffer: Vec<u8> = vec![0; 64];
+        let (res, error) = pool.get_next_out_buffer_checked(&mut receive_buffer);
+        assert_eq!(0, error);
+        assert_eq!(1, res.count);
+
+        pool.receive_blocking_out_buffer();
+        let (res, error) = pool.get_next_out_buffer_checked(&mut receive_buffer);
+        assert_eq!(RECEIVE_ERROR_BUFFER_TOO_SMALL, error);
+        assert_eq!(0, res.count);
+        assert_eq!(2000 + LENGTH_PREFIX_SIZE as u32, res.bytes_written);
+
+        let mut receive_buffer: Vec<u8> = vec![0; res.bytes_written as usize];
+        let (res, error) = pool.get_next_out_buffer_checked(&mut receive_buffer);
+        assert_eq!(0, error);
+        assert_eq!(1, res.count);
+
+        let mut reader = LengthPrefixed::default();
+        let (_channel, _address, range) = reader.read(&receive_buffer);
+        assert_eq!(2000, range.end - range.start);
+    }
+
+    #[test]
+    #[serial]
+    fn test_event_handler() {
+        let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024 * 4);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+
+        let added = Arc::new(AtomicU32::new(0));
+        let handler_added = added.clone();
+        pool.set_connection_event_handler(move |_event_id, connection| {
+            assert_eq!(2, connection.tachyon_id);
+            handler_added.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // created after the handler was set
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+
+        let mut client = TachyonTestClient::create(NetworkAddress::localhost(8002));
+        client.connect();
+        client.client_send_reliable(1, 32);
+        // connected and the message
+        assert_eq!(2, pool.poll_events().count());
+        assert_eq!(1, added.load(Ordering::SeqCst));
+    }
+
+    unsafe extern "C" fn on_delivery(_event: DeliveryEvent) {}
+
+    #[test]
+    #[serial]
+    fn test_delivery_callback() {
+        let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024 * 4);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool_register_delivery_callback(&mut pool, Some(on_delivery));
+
+        // servers created after registering get it too
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+        assert!(pool.get_server(1).unwrap().delivery_event_callback.is_some());
+        assert!(pool.get_server(2).unwrap().delivery_event_callback.is_some());
+    }
+
+    #[test]
+    #[serial]
+    fn test_publish() {
+        let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024 * 4);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+
+        let mut clients = [TachyonTestClient::create(NetworkAddress::localhost(8001)), TachyonTestClient::create(NetworkAddress::localhost(8002))];
+        for client in clients.iter_mut() {
+            client.connect();
+            client.client_send_reliable(1, 32);
+        }
+        assert_eq!(4, pool.poll_events().count());
+
+        let addresses: Vec<NetworkAddress> = pool.connections_by_address.keys().copied().collect();
+        for address in &addresses {
+            assert!(pool.subscribe(1, SendTarget { address: *address, identity_id: 0 }));
+        }
+        pool.subscribe(1, SendTarget { address: NetworkAddress::localhost(9000), identity_id: 0 });
+
+        let mut data: Vec<u8> = vec![7; 500];
+        let result = pool.publish(1, 1, &mut data, 500);
+        assert_eq!(2, result.sent);
+        assert_eq!(1, result.failed);
+        for client in clients.iter_mut() {
+            let res = client.client_receive();
+            assert_eq!(500, res.length);
+            assert_eq!(7, client.receive_buffer[0]);
+        }
+
+        // the unknown address is dropped once connections are updated
+        pool.update_servers();
+        assert_eq!(2, pool.topics.get_subscriber_count(1));
+    }
+
+    #[test]
+    #[serial]
+    fn test_broadcast() {
+        let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024 * 4);
+        let config = TachyonConfig::default();
+        pool.create_server(config, NetworkAddress::localhost(8001), 1);
+        pool.create_server(config, NetworkAddress::localhost(8002), 2);
+
+        let mut clients = [
+            TachyonTestClient::create(NetworkAddress::localhost(8001)),
+            TachyonTestClient::create(NetworkAddress::localhost(8001)),
+            TachyonTestClient::create(NetworkAddress::localhost(8002)),
+        ];
+        for client in clients.iter_mut() {
+            client.connect();
+            client.client_send_reliable(1, 32);
+        }
+        assert_eq!(6, pool.poll_events().count());
+
+        let excluded = *pool.get_server(2).unwrap().connections.keys().next().unwrap();
+        let exclude = [SendTarget { address: excluded, identity_id: 0 }];
+        let mut data: Vec<u8> = vec![3; 200];
+        let result = pool.broadcast(1, &mut data, 200, &exclude);
+        assert_eq!(2, result.sent);
+        assert_eq!(0, result.failed);
+        assert_eq!(200, clients[0].client_receive().length);
+        assert_eq!(200, clients[1].client_receive().length);
+        assert_eq!(0, clients[2].client_receive().length);
+
+        // a broadcast address to send_to_target reaches everyone
+        let target = SendTarget { address: NetworkAddress::broadcast(1), identity_id: 0 };
+        assert_eq!(0, pool.send_to_target(1, target, &mut data, 100).error);
+        for client in clients.iter_mut() {
+            assert_eq!(100, client.client_receive().length);
+        }
+
+        let mut sender = PoolUnreliableSender::create();
+        sender.build(&mut pool);
+        assert_eq!(2, sender.broadcast(&mut data, 50, &exclude).sent);
+        assert_eq!(50, clients[1].client_receive().length);
+        assert_eq!(0, clients[2].client_receive().length);
+    }
+
+    #[test]
+    #[serial]
+    fn test_unknown_target() {
+        let mut pool = Pool::create(4, 1024 * 1024, 1024 * 1024 * 4);
+        let config = TachyonConfig::default();
+        assert!(pool.try_create_server(config, NetworkAddress::localhost(8001), 1).is_ok());
+        match pool.try_create_server(config, NetworkAddress::localhost(8002), 1) {
+            Err(TachyonError::DuplicateServer) => {}
+            _ => panic!("expected DuplicateServer"),
+        }
+
+        let mut data: Vec<u8> = vec![0; 32];
+        let target = SendTarget { address: NetworkAddress::localhost(9000), identity_id: 0 };
+        assert_eq!(SEND_ERROR_UNKNOWN_TARGET, pool.send_to_target(1, target, &mut data, 32).error);
+        match pool.try_send_to_target(1, target, &mut data, 32) {
+            Err(TachyonError::UnknownTarget) => {}
+            _ => panic!("expected UnknownTarget"),
+        }
+    }
+
     #[test]
     #[serial]
     fn test_receive() {
@@ -1,6 +1,6 @@
 
 use crate::tachyon::*;
-use super::{pool::{Pool, PoolServerRef, OutBufferCounts, SendTarget}, ffi::copy_send_result};
+use super::{pool::{Pool, PoolServerRef, OutBufferCounts, SendTarget}, ffi::{copy_send_result, dictionary_slice, exclude_slice}, delivery::{DeliveryEvent, DeliveryEventCallback}, topic::PublishResult};
 
 #[no_mangle]
 pub extern "C" fn pool_create(max_servers: u8, receive_buffer_len: u32, out_buffer_len: u32) -> *mut Pool {
@@ -43,6 +43,39 @@ pub extern "C" fn pool_configure_channel(pool_ptr: *mut Pool, server_id: u16, ch
     return -1;
 }
 
+#[no_mangle]
+pub extern "C" fn pool_remove_channel(pool_ptr: *mut Pool, server_id: u16, channel_id: u8) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    if let Some(tachyon) = pool.get_server(server_id) {
+        if tachyon.remove_channel(channel_id) {
+            return 1;
+        }
+    }
+    return -1;
+}
+
+#[no_mangle]
+pub extern "C" fn pool_set_compression_dictionary(pool_ptr: *mut Pool, server_id: u16, channel_id: u8, dictionary: *const u8, length: i32) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    if let Some(tachyon) = pool.get_server(server_id) {
+        tachyon.set_compression_dictionary(channel_id, dictionary_slice(dictionary, length));
+        return 1;
+    }
+    return -1;
+}
+
+#[no_mangle]
+pub extern "C" fn pool_take_channel_mismatch(pool_ptr: *mut Pool, mismatch: *mut ChannelMismatch) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    if let Some(channel_mismatch) = pool.take_channel_mismatch() {
+        unsafe {
+            (*mismatch) = channel_mismatch;
+        }
+        return 1;
+    }
+    return 0;
+}
+
 #[no_mangle]
 pub extern "C" fn pool_get_available(pool_ptr: *mut Pool, pool_ref_ptr: *mut PoolServerRef) -> i32 {
     let pool = unsafe { &mut *pool_ptr };
@@ -81,10 +114,7 @@ pub extern "C" fn pool_set_identity(pool_ptr: *mut Pool, server_id: u16, id: u32
 #[no_mangle]
 pub extern "C" fn pool_update_servers(pool_ptr: *mut Pool) {
     let pool = unsafe { &mut *pool_ptr };
-    for server in pool.servers.values_mut() {
-        server.update();
-    }
-    pool.build_connection_maps();
+    pool.update_servers();
 }
 
 #[no_mangle]
@@ -103,6 +133,27 @@ pub extern "C" fn pool_register_callbacks(pool_ptr: *mut Pool, identity_event_ca
     }
 }
 
+#[no_mangle]
+pub extern "C" fn pool_register_delivery_callback(pool_ptr: *mut Pool, delivery_event_callback: Option<DeliveryEventCallback>) {
+    let pool = unsafe { &mut *pool_ptr };
+    pool.delivery_event_callback = delivery_event_callback;
+    for server in pool.servers.values_mut() {
+        server.delivery_event_callback = delivery_event_callback;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn pool_take_delivery_event(pool_ptr: *mut Pool, event: *mut DeliveryEvent) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    if let Some(delivery_event) = pool.take_delivery_event() {
+        unsafe {
+            (*event) = delivery_event;
+        }
+        return 1;
+    }
+    return 0;
+}
+
 #[no_mangle]
 pub extern "C" fn pool_receive_blocking(pool_ptr: *mut Pool) {
     let pool = unsafe { &mut *pool_ptr };
@@ -119,6 +170,19 @@ pub extern "C" fn pool_get_next_out_buffer(pool_ptr: *mut Pool, receive_buffer_p
     }
 }
 
+// Same as pool_get_next_out_buffer with the receive buffer length passed in, for buffers grown after a
+// RECEIVE_ERROR_BUFFER_TOO_SMALL result.  Returns the error, with result.bytes_written the size needed.
+#[no_mangle]
+pub extern "C" fn pool_get_next_out_buffer_len(pool_ptr: *mut Pool, receive_buffer_ptr: *mut u8, receive_buffer_len: u32, result: *mut OutBufferCounts) -> u32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(receive_buffer_ptr, receive_buffer_len as usize) };
+    let (res, error) = pool.get_next_out_buffer_checked(slice);
+    unsafe {
+        (*result) = res;
+    }
+    return error;
+}
+
 #[no_mangle]
 pub extern "C" fn pool_receive(pool_ptr: *mut Pool) -> i32 {
     let pool = unsafe { &mut *pool_ptr };
@@ -147,4 +211,63 @@ pub extern "C" fn pool_send_to(pool_ptr: *mut Pool, channel: u8, target_ptr: *co
     copy_send_result(result, ret);
 }
 
+#[no_mangle]
+pub extern "C" fn pool_subscribe(pool_ptr: *mut Pool, topic: u32, target_ptr: *const SendTarget) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    return pool.subscribe(topic, target) as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn pool_unsubscribe(pool_ptr: *mut Pool, topic: u32, target_ptr: *const SendTarget) -> i32 {
+    let pool = unsafe { &mut *pool_ptr };
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    return pool.unsubscribe(topic, target) as i32;
+}
+
+#[no_mangle]
+pub extern "C" fn pool_publish(pool_ptr: *mut Pool, topic: u32, channel: u8, data: *mut u8, length: i32, ret: *mut PublishResult) {
+    let pool = unsafe { &mut *pool_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+
+    let result = pool.publish(topic, channel, slice, length);
+    unsafe {
+        *ret = result;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn pool_broadcast(pool_ptr: *mut Pool, channel: u8, data: *mut u8, length: i32, exclude_ptr: *const SendTarget, exclude_count: i32, ret: *mut PublishResult) {
+    let pool = unsafe { &mut *pool_ptr };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+    let exclude = exclude_slice(exclude_ptr, exclude_count);
+
+    let result = pool.broadcast(channel, slice, length, exclude);
+    unsafe {
+        *ret = result;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn pool_send_reliable_ttl_to(pool_ptr: *mut Pool, channel: u8, target_ptr: *const SendTarget, data: *mut u8, length: i32, ttl: u32, ret: *mut TachyonSendResult) {
+    let pool = unsafe { &mut *pool_ptr };
+
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+
+    let result = pool.send_reliable_ttl_to_target(channel, target, slice, length, ttl);
+    copy_send_result(result, ret);
+}
+
+#[no_mangle]
+pub extern "C" fn pool_send_unreliable_to(pool_ptr: *mut Pool, channel: u8, target_ptr: *const SendTarget, data: *mut u8, length: i32, ret: *mut TachyonSendResult) {
+    let pool = unsafe { &mut *pool_ptr };
+
+    let target: SendTarget = unsafe { std::ptr::read(target_ptr as *const _) };
+    let slice = unsafe { std::slice::from_raw_parts_mut(data, length as usize) };
+
+    let result = pool.send_unreliable_to_target(channel, target, slice, length);
+    copy_send_result(result, ret);
+}
+
 
@@ -1,13 +1,16 @@
 use rustc_hash::FxHashMap;
 
 use super::{
-    pool::{Pool, SendTarget}, TachyonSendResult, network_address::NetworkAddress, connection::Connection, unreliable_sender::UnreliableSender, ffi::copy_send_result
+    pool::{Pool, SendTarget}, TachyonSendResult, network_address::NetworkAddress, connection::Connection, unreliable_sender::UnreliableSender, ffi::{copy_send_result, exclude_slice},
+    topic::PublishResult
 };
 
 pub struct PoolUnreliableSender {
     pub identity_to_conn_map: FxHashMap<u32, Connection>,
     pub address_to_conn_map: FxHashMap<NetworkAddress, Connection>,
-    pub senders: FxHashMap<u16, UnreliableSender>
+    pub senders: FxHashMap<u16, UnreliableSender>,
+    // connections a broadcast goes to, linked ones only on servers using identities
+    pub broadcast_connections: Vec<Connection>
 }
 
 impl PoolUnreliableSender {
@@ -15,11 +18,15 @@ impl PoolUnreliableSender {
         PoolUnreliableSender {
             identity_to_conn_map: FxHashMap::default(),
             address_to_conn_map: FxHashMap::default(),
-            senders: FxHashMap::default()
+            senders: FxHashMap::default(),
+            broadcast_connections: Vec::new()
         }
     }
 
     pub fn send_to_target(&mut self, target: SendTarget, data: &mut [u8], length: i32) -> TachyonSendResult {
+        if target.identity_id == 0 && target.address.is_broadcast() {
+            return self.broadcast(data, length, &[]).to_send_result();
+        }
         if target.identity_id > 0 {
             return self.send_to_identity(target.identity_id, data, length);
         } else {
@@ -45,10 +52,26 @@ impl PoolUnreliableSender {
         return TachyonSendResult::default();
     }
 
+    // unreliable to every connection as of the last build, except those matching exclude
+    pub fn broadcast(&mut self, data: &mut [u8], length: i32, exclude: &[SendTarget]) -> PublishResult {
+        let mut result = PublishResult::default();
+        for conn in &self.broadcast_connections {
+            if exclude.iter().any(|target| target.matches(conn)) {
+                continue;
+            }
+            if let Some(sender) = self.senders.get_mut(&conn.tachyon_id) {
+                let send_result = sender.send(conn.address, data, length as usize);
+                result.count(send_result.error, send_result.sent_len);
+            }
+        }
+        return result;
+    }
+
     pub fn build(&mut self, pool: &mut Pool) {
         self.address_to_conn_map.clear();
         self.identity_to_conn_map.clear();
         self.senders.clear();
+        self.broadcast_connections.clear();
         
         for server in pool.servers.values() {
             if !self.senders.contains_key(&server.id) {
@@ -57,6 +80,9 @@ impl PoolUnreliableSender {
                 }
             }
             for conn in server.connections.values() {
+                if server.config.use_identity == 0 || conn.identity.is_linked() {
+                    self.broadcast_connections.push(*conn);
+                }
                 self.address_to_conn_map.insert(conn.address, *conn);
                 if conn.identity.id > 0 {
                     self.identity_to_conn_map.insert(conn.identity.id, *conn);
@@ -99,3 +125,15 @@ pub extern "C" fn pool_unreliable_sender_send(sender_ptr: *mut PoolUnreliableSen
     let result =  sender.send_to_target(target, data, length);
     copy_send_result(result, ret);
 }
+
+#[no_mangle]
+pub extern "C" fn pool_unreliable_sender_broadcast(sender_ptr: *mut PoolUnreliableSender, data_ptr: *mut u8, length: i32, exclude_ptr: *const SendTarget, exclude_count: i32, ret: *mut PublishResult) {
+    let sender = unsafe { &mut *sender_ptr };
+    let data = unsafe { std::slice::from_raw_parts_mut(data_ptr, length as usize) };
+    let exclude = exclude_slice(exclude_ptr, exclude_count);
+
+    let result = sender.broadcast(data, length, exclude);
+    unsafe {
+        *ret = result;
+    }
+}
@@ -2,6 +2,8 @@ use super::network_address::NetworkAddress;
 
 pub const RECEIVE_ERROR_UNKNOWN: u32 = 1;
 pub const RECEIVE_ERROR_CHANNEL: u32 = 2;
+// length is the size needed, the message stays queued for the next receive
+pub const RECEIVE_ERROR_BUFFER_TOO_SMALL: u32 = 3;
 
 pub enum ReceiveResult {
     Reliable {
@@ -15,6 +17,8 @@ pub enum ReceiveResult {
     UnReliable {
         received_len: usize,
         network_address: NetworkAddress,
+        // 0 if not sent on a channel
+        channel_id: u8,
     },
 }
 
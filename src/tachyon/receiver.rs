@@ -1,13 +1,19 @@
 
 use std::collections::VecDeque;
 
-use super::{nack::Nack, sequence::*, sequence_buffer::SequenceBuffer, channel::RECEIVE_WINDOW_SIZE_DEFAULT, byte_buffer_pool::{ByteBuffer, ByteBufferPool}};
+use super::{header::MESSAGE_TYPE_NONE, nack::Nack, sequence::*, sequence_buffer::SequenceBuffer, channel::{RECEIVE_WINDOW_SIZE_DEFAULT, CHANNEL_MODE_RELIABLE, CHANNEL_MODE_UNRELIABLE_SEQUENCED, CHANNEL_MODE_RELIABLE_LATEST}, byte_buffer_pool::{ByteBuffer, ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT, POOL_SIZE_DEFAULT}};
 
 const RECEIVE_BUFFER_SIZE: u16 = 1024;
 
+// most skipped sequences reported in one ack datagram, longer lists are split over several
+pub const MAX_REPORTED_SKIPPED: usize = 64;
+
 
 pub struct Receiver {
     pub is_ordered: bool,
+    pub mode: u32,
+    // newest sequence published on sequenced and latest channels
+    pub latest_sequence: u16,
     pub receive_window_size: u32,
     pub last_sequence: u16,
     pub current_sequence: u16,
@@ -18,11 +24,17 @@ pub struct Receiver {
     pub nack_list: Vec<Nack>,
     pub nack_queue: VecDeque<Nack>,
     pub skipped_sequences: u64,
+    // Recently skipped sequences.  Acks cover them but they were never received, so they are sent along with acks
+    // and the sender doesn't count them as delivered.
+    pub skipped: VecDeque<u16>,
+    // older then the newest already published on sequenced and latest channels
+    pub dropped_sequences: u64,
     pub buffer_pool: ByteBufferPool
 }
 
 impl Receiver {
-    pub fn create(is_ordered: bool, receive_window_size: u32) -> Self {
+    // only reliable channels deliver in order, the other modes deliver the newest message as soon as it arrives
+    pub fn create(is_ordered: bool, mode: u32, receive_window_size: u32, buffer_size: usize) -> Self {
         let mut buffered: SequenceBuffer<ByteBuffer> = SequenceBuffer {
             values: Vec::new(),
             partition_by: RECEIVE_BUFFER_SIZE,
@@ -37,7 +49,9 @@ impl Receiver {
         };
 
         let receiver = Receiver {
-            is_ordered,
+            is_ordered: is_ordered && mode == CHANNEL_MODE_RELIABLE,
+            mode,
+            latest_sequence: 0,
             receive_window_size,
             last_sequence: 0,
             current_sequence: 0,
@@ -47,15 +61,17 @@ impl Receiver {
             resend_list: Vec::new(),
             nack_list: Vec::new(),
             skipped_sequences: 0,
+            skipped: VecDeque::new(),
+            dropped_sequences: 0,
             nack_queue: VecDeque::new(),
-            buffer_pool: ByteBufferPool::default()
+            buffer_pool: ByteBufferPool::create(buffer_size, POOL_SIZE_DEFAULT)
         };
 
         return receiver;
     }
 
     pub fn default(is_ordered: bool) -> Self {
-        return Receiver::create(is_ordered, RECEIVE_WINDOW_SIZE_DEFAULT);
+        return Receiver::create(is_ordered, CHANNEL_MODE_RELIABLE, RECEIVE_WINDOW_SIZE_DEFAULT, BYTE_BUFFER_SIZE_DEFAULT);
     }
 
     pub fn calculate_current_in_window(current: u16, last: u16) -> u16 {
@@ -91,6 +107,41 @@ impl Receiver {
         }
     }
 
+    // Kept in sequence order, fragments of dropped groups can be ahead of current.  Entries too old for the sender
+    // to still have a buffer for are dropped.  Nothing else is, the ack covers every entry so dropping one would
+    // report it delivered.
+    pub fn set_skipped(&mut self, sequence: u16) {
+        let mut index = self.skipped.len();
+        while index > 0 {
+            let previous = self.skipped[index - 1];
+            if previous == sequence {
+                return;
+            }
+            if Sequence::is_greater_then(sequence, previous) {
+                break;
+            }
+            index -= 1;
+        }
+        self.skipped.insert(index, sequence);
+
+        while let Some(oldest) = self.skipped.front() {
+            if Sequence::is_greater_then(*oldest, self.current_sequence) || Sequence::distance(*oldest, self.current_sequence) < RECEIVE_BUFFER_SIZE {
+                break;
+            }
+            self.skipped.pop_front();
+        }
+    }
+
+    // The ack that can be sent without the skipped list, the sequence before the oldest one skipped.
+    pub fn get_safe_ack(&self) -> u16 {
+        if let Some(oldest) = self.skipped.front() {
+            if !Sequence::is_greater_then(*oldest, self.current_sequence) {
+                return Sequence::previous_sequence(*oldest);
+            }
+        }
+        return self.current_sequence;
+    }
+
     pub fn return_buffer(&mut self, byte_buffer: ByteBuffer) {
         self.buffer_pool.return_buffer(byte_buffer);
     }
@@ -99,6 +150,11 @@ impl Receiver {
         return self.published.pop_front();
     }
 
+    // put back a taken message so it's the next one taken
+    pub fn return_published(&mut self, byte_buffer: ByteBuffer) {
+        self.published.push_front(byte_buffer);
+    }
+
     fn is_buffered(&self, sequence: u16) -> bool {
         return self.buffered.is_some(sequence);
     }
@@ -122,11 +178,16 @@ impl Receiver {
     // in big steps for example or we would leave a bunch of entries < current still marked as received.
 
     pub fn receive_packet(&mut self, sequence: u16, data: &[u8], length: usize) -> bool {
+        if self.mode == CHANNEL_MODE_UNRELIABLE_SEQUENCED {
+            return self.receive_sequenced(sequence, data, length);
+        }
+
         // if the difference between current/last is greater then the window, increment current.
         if Receiver::should_increment_current(self.current_sequence, self.last_sequence, self.receive_window_size) {
             self.received.take(self.current_sequence);
             self.current_sequence = Sequence::next_sequence(self.current_sequence);
             self.skipped_sequences += 1;
+            self.set_skipped(self.current_sequence);
         }
 
         if !Sequence::is_greater_then(sequence, self.current_sequence) {
@@ -157,6 +218,50 @@ impl Receiver {
         return true;
     }
 
+    // Unreliable sequenced.  Anything not newer then the last one published is dropped.  Current and last stay
+    // where they are, nothing is nacked or acked.
+    fn receive_sequenced(&mut self, sequence: u16, data: &[u8], length: usize) -> bool {
+        if !Sequence::is_greater_then(sequence, self.latest_sequence) {
+            self.dropped_sequences += 1;
+            return false;
+        }
+
+        self.latest_sequence = sequence;
+
+        let mut byte_buffer = self.buffer_pool.get_buffer(length);
+        byte_buffer.get_mut()[0..length].copy_from_slice(&data[0..length]);
+        self.published.push_back(byte_buffer);
+        return true;
+    }
+
+    // Reliable latest messages are received like unordered ones, but only a message newer then the last one published
+    // is published, and it replaces anything still waiting to be taken.
+    fn publish_buffer(&mut self, sequence: u16, byte_buffer: ByteBuffer) {
+        if self.mode != CHANNEL_MODE_RELIABLE_LATEST {
+            self.published.push_back(byte_buffer);
+            return;
+        }
+
+        // nones fill in superseded sequences, there is nothing to deliver
+        if byte_buffer.get()[0] == MESSAGE_TYPE_NONE {
+            self.buffer_pool.return_buffer(byte_buffer);
+            return;
+        }
+
+        if !Sequence::is_greater_then(sequence, self.latest_sequence) {
+            self.dropped_sequences += 1;
+            self.buffer_pool.return_buffer(byte_buffer);
+            return;
+        }
+
+        self.latest_sequence = sequence;
+        while let Some(older) = self.published.pop_front() {
+            self.dropped_sequences += 1;
+            self.buffer_pool.return_buffer(older);
+        }
+        self.published.push_back(byte_buffer);
+    }
+
     pub fn publish(&mut self) {
         // walk from current to last and move buffered into published
         // increment current sequence until we hit a missing sequence.
@@ -180,7 +285,7 @@ impl Receiver {
                 if self.is_buffered(seq) {
                     match self.buffered.take(seq) {
                         Some(byte_buffer) => {
-                            self.published.push_back(byte_buffer);
+                            self.publish_buffer(seq, byte_buffer);
                         }
                         None => {}
                     }
@@ -362,6 +467,32 @@ mod tests {
 
         assert!(!channel.receive_packet(1, &data[..], 32));
         assert_eq!(2, channel.current_sequence);
+
+        // acks cover skipped sequences, so they are reported
+        assert_eq!(vec![1, 2], channel.skipped.iter().copied().collect::<Vec<u16>>());
+        assert_eq!(0, channel.get_safe_ack());
+
+        // kept until too old for the sender to have, however many there are
+        for sequence in 3..1000 {
+            channel.current_sequence = sequence;
+            channel.set_skipped(sequence);
+        }
+        assert_eq!(999, channel.skipped.len());
+        channel.current_sequence = 1025;
+        channel.set_skipped(1025);
+        assert_eq!(2, *channel.skipped.front().unwrap());
+
+        // dropped fragment groups can be older or ahead of current, and already listed
+        channel.skipped.clear();
+        channel.set_skipped(1020);
+        channel.set_skipped(1030);
+        channel.set_skipped(1010);
+        channel.set_skipped(1020);
+        assert_eq!(vec![1010, 1020, 1030], channel.skipped.iter().copied().collect::<Vec<u16>>());
+        assert_eq!(1009, channel.get_safe_ack());
+        channel.skipped.clear();
+        channel.set_skipped(1030);
+        assert_eq!(1025, channel.get_safe_ack());
     }
 
     #[test]
@@ -560,4 +691,47 @@ mod tests {
         assert!(channel.take_published().is_none());
         assert_eq!(0, channel.published.len());
     }
+
+    #[test]
+    fn sequenced_drops_older() {
+        let mut channel = Receiver::create(true, CHANNEL_MODE_UNRELIABLE_SEQUENCED, RECEIVE_WINDOW_SIZE_DEFAULT, BYTE_BUFFER_SIZE_DEFAULT);
+        assert!(!channel.is_ordered);
+        let data: Vec<u8> = vec![0; 1024];
+        assert!(channel.receive_packet(1, &data[..], 32));
+        assert!(channel.receive_packet(5, &data[..], 32));
+        assert!(!channel.receive_packet(3, &data[..], 32));
+        assert!(!channel.receive_packet(5, &data[..], 32));
+        assert_eq!(2, channel.published.len());
+        assert_eq!(2, channel.dropped_sequences);
+
+        // nothing to nack for the gap
+        assert_eq!(0, channel.create_nacks());
+
+        // wraps
+        channel.latest_sequence = 65534;
+        assert!(channel.receive_packet(0, &data[..], 32));
+    }
+
+    #[test]
+    fn latest_publishes_newest() {
+        let mut channel = Receiver::create(false, CHANNEL_MODE_RELIABLE_LATEST, RECEIVE_WINDOW_SIZE_DEFAULT, BYTE_BUFFER_SIZE_DEFAULT);
+        let data: Vec<u8> = vec![0; 1024];
+        channel.receive_packet(1, &data[..], 32);
+        channel.receive_packet(3, &data[..], 32);
+        assert_eq!(1, channel.published.len());
+        assert_eq!(3, channel.latest_sequence);
+
+        // still nacked so the window moves, but not published
+        assert_eq!(1, channel.create_nacks());
+        assert!(channel.receive_packet(2, &data[..], 32));
+        assert_eq!(1, channel.published.len());
+        assert_eq!(3, channel.current_sequence);
+
+        let mut none: Vec<u8> = vec![0; 32];
+        none[0] = MESSAGE_TYPE_NONE;
+        channel.receive_packet(5, &none[..], 32);
+        channel.receive_packet(4, &data[..], 32);
+        assert_eq!(1, channel.published.len());
+        assert_eq!(4, channel.latest_sequence);
+    }
 }
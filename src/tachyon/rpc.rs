@@ -0,0 +1,329 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+
+use super::{
+    error::TachyonError, event::Event, int_buffer::IntBuffer, network_address::NetworkAddress, pool::{Pool, SendTarget},
+    Tachyon, TachyonSendResult,
+};
+
+pub const RPC_REQUEST: u8 = 1;
+pub const RPC_RESPONSE: u8 = 2;
+pub const RPC_ERROR: u8 = 3;
+
+// kind u8, request id u32, method id u16
+pub const RPC_HEADER_SIZE: usize = 7;
+
+// error response codes, handlers can return any other value
+pub const RPC_ERROR_NO_METHOD: u32 = 1;
+
+pub type RequestId = u32;
+
+// What a handler gets besides the payload, respond to address to reach the caller
+#[derive(Clone, Copy, Debug)]
+pub struct RpcRequest {
+    pub request_id: RequestId,
+    pub method_id: u16,
+    pub address: NetworkAddress,
+    // 0 when identities are not used
+    pub identity: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RpcResult {
+    Response(Vec<u8>),
+    // the handler's error code, or RPC_ERROR_NO_METHOD
+    Error(u32),
+    Timeout,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RpcCompletion {
+    pub request_id: RequestId,
+    pub method_id: u16,
+    pub result: RpcResult,
+}
+
+// Tachyon and Pool, so one Rpc works with either
+pub trait RpcSender {
+    fn send_rpc(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> Result<TachyonSendResult, TachyonError>;
+}
+
+impl RpcSender for Tachyon {
+    fn send_rpc(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> Result<TachyonSendResult, TachyonError> {
+        return self.try_send_to_target(channel, target, data, length);
+    }
+}
+
+impl RpcSender for Pool {
+    fn send_rpc(&mut self, channel: u8, target: SendTarget, data: &mut [u8], length: usize) -> Result<TachyonSendResult, TachyonError> {
+        return self.try_send_to_target(channel, target, data, length as i32);
+    }
+}
+
+type RpcHandler = Box<dyn FnMut(RpcRequest, &[u8]) -> Result<Vec<u8>, u32> + Send>;
+
+struct PendingCall {
+    method_id: u16,
+    // responses have to come from the target called
+    target: SendTarget,
+    sent_at: Instant,
+}
+
+impl PendingCall {
+    fn is_from(&self, address: NetworkAddress, identity: u32) -> bool {
+        if self.target.identity_id > 0 {
+            return identity == self.target.identity_id;
+        }
+        return address == self.target.address;
+    }
+}
+
+// Request/response over a reliable channel that carries nothing else.  Every message has a header with it's
+// kind, a request id and the method id.  Requests run the method's handler and it's result goes back as a
+// response or an error response.  Responses are matched to their call by request id and the target it was sent to,
+// and queued as completions, calls without a response after the timeout complete with RpcResult::Timeout and a
+// late response is dropped.
+// Both sides can call and handle.
+pub struct Rpc {
+    pub channel: u8,
+    pub timeout: Duration,
+    next_request_id: RequestId,
+    pending: FxHashMap<RequestId, PendingCall>,
+    handlers: FxHashMap<u16, RpcHandler>,
+    completions: VecDeque<RpcCompletion>,
+    send_buffer: Vec<u8>,
+}
+
+impl Rpc {
+    pub fn create(channel: u8, timeout: Duration) -> Self {
+        let rpc = Rpc {
+            channel,
+            timeout,
+            next_request_id: 1,
+            pending: FxHashMap::default(),
+            handlers: FxHashMap::default(),
+            completions: VecDeque::new(),
+            send_buffer: vec![0; 1024],
+        };
+        return rpc;
+    }
+
+    // replaces any handler for the method.  Return the response payload, or Err with a code for an error response.
+    pub fn set_handler<F>(&mut self, method_id: u16, handler: F) where F: FnMut(RpcRequest, &[u8]) -> Result<Vec<u8>, u32> + Send + 'static {
+        self.handlers.insert(method_id, Box::new(handler));
+    }
+
+    pub fn remove_handler(&mut self, method_id: u16) -> bool {
+        return self.handlers.remove(&method_id).is_some();
+    }
+
+    pub fn pending_count(&self) -> usize {
+        return self.pending.len();
+    }
+
+    pub fn call<S>(&mut self, sender: &mut S, target: SendTarget, method_id: u16, payload: &[u8]) -> Result<RequestId, TachyonError> where S: RpcSender {
+        let request_id = self.next_request_id;
+        self.send(sender, target, RPC_REQUEST, request_id, method_id, payload)?;
+
+        self.next_request_id = self.next_request_id.wrapping_add(1);
+        if self.next_request_id == 0 {
+            self.next_request_id = 1;
+        }
+        self.pending.insert(request_id, PendingCall { method_id, target, sent_at: Instant::now() });
+        return Ok(request_id);
+    }
+
+    fn send<S>(&mut self, sender: &mut S, target: SendTarget, kind: u8, request_id: RequestId, method_id: u16, payload: &[u8]) -> Result<TachyonSendResult, TachyonError>
+    where
+        S: RpcSender,
+    {
+        let length = RPC_HEADER_SIZE + payload.len();
+        if self.send_buffer.len() < length {
+            self.send_buffer.resize(length, 0);
+        }
+        let mut writer = IntBuffer { index: 0 };
+        writer.write_u8(kind, &mut self.send_buffer);
+        writer.write_u32(request_id, &mut self.send_buffer);
+        writer.write_u16(method_id, &mut self.send_buffer);
+        self.send_buffer[RPC_HEADER_SIZE..length].copy_from_slice(payload);
+        return sender.send_rpc(self.channel, target, &mut self.send_buffer, length);
+    }
+
+    // Handles a message received on the rpc channel, Ok(false) if it's for another channel.  Requests are answered
+    // through sender, which should be the Tachyon or Pool it was received on.
+    pub fn dispatch<S>(&mut self, sender: &mut S, channel: u8, address: NetworkAddress, identity: u32, data: &[u8]) -> Result<bool, TachyonError>
+    where
+        S: RpcSender,
+    {
+        if channel != self.channel {
+            return Ok(false);
+        }
+        if data.len() < RPC_HEADER_SIZE {
+            return Err(TachyonError::Decode(String::from("rpc message shorter then the header")));
+        }
+
+        let mut reader = IntBuffer { index: 0 };
+        let kind = reader.read_u8(data);
+        let request_id = reader.read_u32(data);
+        let method_id = reader.read_u16(data);
+        let payload = &data[RPC_HEADER_SIZE..];
+
+        match kind {
+            RPC_REQUEST => {
+                let request = RpcRequest { request_id, method_id, address, identity };
+                let result = match self.handlers.get_mut(&method_id) {
+                    Some(handler) => handler(request, payload),
+                    None => Err(RPC_ERROR_NO_METHOD),
+                };
+
+                let target = SendTarget { address, identity_id: 0 };
+                match result {
+                    Ok(response) => {
+                        self.send(sender, target, RPC_RESPONSE, request_id, method_id, &response)?;
+                    }
+                    Err(code) => {
+                        let mut code_data: Vec<u8> = vec![0; 4];
+                        let mut writer = IntBuffer { index: 0 };
+                        writer.write_u32(code, &mut code_data);
+                        self.send(sender, target, RPC_ERROR, request_id, method_id, &code_data)?;
+                    }
+                }
+            }
+            RPC_RESPONSE | RPC_ERROR => {
+                // unknown ids are responses that already timed out, others could be guesses from another connection
+                match self.pending.get(&request_id) {
+                    Some(call) if call.method_id == method_id && call.is_from(address, identity) => {}
+                    _ => {
+                        return Ok(true);
+                    }
+                }
+                self.pending.remove(&request_id);
+
+                let result = if kind == RPC_RESPONSE {
+                    RpcResult::Response(payload.to_vec())
+                } else if payload.len() >= 4 {
+                    let mut reader = IntBuffer { index: 0 };
+                    RpcResult::Error(reader.read_u32(payload))
+                } else {
+                    RpcResult::Error(0)
+                };
+                self.completions.push_back(RpcCompletion { request_id, method_id, result });
+            }
+            _ => {
+                return Err(TachyonError::Decode(format!("unknown rpc message kind {}", kind)));
+            }
+        }
+        return Ok(true);
+    }
+
+    // for poll_events, Ok(false) for events that are not rpc messages
+    pub fn dispatch_event<S>(&mut self, sender: &mut S, event: &Event) -> Result<bool, TachyonError> where S: RpcSender {
+        match event {
+            Event::Message { channel, address, identity, data } => {
+                return self.dispatch(sender, *channel, *address, *identity, data);
+            }
+            _ => {
+                return Ok(false);
+            }
+        }
+    }
+
+    // next finished call, calls past the timeout are completed here
+    pub fn take_completion(&mut self) -> Option<RpcCompletion> {
+        if self.completions.is_empty() && self.pending.len() > 0 {
+            let timeout = self.timeout;
+            let mut expired: Vec<(RequestId, u16)> = Vec::new();
+            for (request_id, call) in &self.pending {
+                if call.sent_at.elapsed() >= timeout {
+                    expired.push((*request_id, call.method_id));
+                }
+            }
+            expired.sort();
+            for (request_id, method_id) in expired {
+                self.pending.remove(&request_id);
+                self.completions.push_back(RpcCompletion { request_id, method_id, result: RpcResult::Timeout });
+            }
+        }
+        return self.completions.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::tachyon::tachyon_test::TachyonTest;
+
+    use super::*;
+
+    fn dispatch_all(rpc: &mut Rpc, tachyon: &mut Tachyon) {
+        let events: Vec<Event> = tachyon.poll_events().collect();
+        for event in &events {
+            rpc.dispatch_event(tachyon, event).unwrap();
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_rpc() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        let target = SendTarget { address: test.client_address, identity_id: 0 };
+
+        let mut server = Rpc::create(1, Duration::from_secs(5));
+        server.set_handler(1, |request: RpcRequest, payload: &[u8]| {
+            assert_eq!(1, request.method_id);
+            let mut response = payload.to_vec();
+            response.reverse();
+            return Ok(response);
+        });
+        server.set_handler(2, |_, _| Err(42));
+
+        let mut client = Rpc::create(1, Duration::from_secs(5));
+        let echo = client.call(&mut test.client, target, 1, &[1, 2, 3]).unwrap();
+        let failed = client.call(&mut test.client, target, 2, &[]).unwrap();
+        let missing = client.call(&mut test.client, target, 3, &[]).unwrap();
+        assert_eq!(3, client.pending_count());
+        assert!(client.take_completion().is_none());
+
+        dispatch_all(&mut server, &mut test.server);
+        dispatch_all(&mut client, &mut test.client);
+        assert_eq!(0, client.pending_count());
+
+        let mut completions: Vec<RpcCompletion> = Vec::new();
+        while let Some(completion) = client.take_completion() {
+            completions.push(completion);
+        }
+        completions.sort_by_key(|completion| completion.request_id);
+        assert_eq!(RpcCompletion { request_id: echo, method_id: 1, result: RpcResult::Response(vec![3, 2, 1]) }, completions[0]);
+        assert_eq!(RpcCompletion { request_id: failed, method_id: 2, result: RpcResult::Error(42) }, completions[1]);
+        assert_eq!(RpcCompletion { request_id: missing, method_id: 3, result: RpcResult::Error(RPC_ERROR_NO_METHOD) }, completions[2]);
+
+        // a response from somewhere other then the target is dropped
+        let spoofed = client.call(&mut test.client, target, 1, &[5]).unwrap();
+        let mut response: Vec<u8> = vec![0; RPC_HEADER_SIZE];
+        let mut writer = IntBuffer { index: 0 };
+        writer.write_u8(RPC_RESPONSE, &mut response);
+        writer.write_u32(spoofed, &mut response);
+        writer.write_u16(1, &mut response);
+        assert!(client.dispatch(&mut test.client, 1, NetworkAddress::localhost(9999), 0, &response).unwrap());
+        assert_eq!(1, client.pending_count());
+        assert!(client.take_completion().is_none());
+        dispatch_all(&mut server, &mut test.server);
+        dispatch_all(&mut client, &mut test.client);
+        assert_eq!(RpcResult::Response(vec![5]), client.take_completion().unwrap().result);
+
+        // times out, and the late response is dropped
+        client.timeout = Duration::from_millis(10);
+        let late = client.call(&mut test.client, target, 1, &[4]).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(RpcCompletion { request_id: late, method_id: 1, result: RpcResult::Timeout }, client.take_completion().unwrap());
+        dispatch_all(&mut server, &mut test.server);
+        dispatch_all(&mut client, &mut test.client);
+        assert!(client.take_completion().is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Instant;
+
+// bytes a channel with weight 1 can send per scheduling round
+pub const SCHEDULER_QUANTUM: i64 = 1024;
+// a bucket can save up at most this many milliseconds of it's rate
+const BUCKET_BURST_MILLIS: u64 = 100;
+
+// Send rate limit for a connection, bytes per second.  Tokens refill continuously and can go negative
+// when a send overshoots, which just delays the next one.
+#[derive(Clone, Copy)]
+pub struct TokenBucket {
+    pub rate: u32,
+    pub tokens: i64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn create(rate: u32) -> Self {
+        let bucket = TokenBucket {
+            rate,
+            tokens: TokenBucket::capacity_for(rate),
+            last_refill: Instant::now(),
+        };
+        return bucket;
+    }
+
+    fn capacity_for(rate: u32) -> i64 {
+        return std::cmp::max(rate as i64 * BUCKET_BURST_MILLIS as i64 / 1000, SCHEDULER_QUANTUM);
+    }
+
+    pub fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_micros() as i64;
+        let added = elapsed * self.rate as i64 / 1_000_000;
+        // don't lose fractions of a byte to frequent refills
+        if added == 0 {
+            return;
+        }
+        self.tokens = std::cmp::min(self.tokens + added, TokenBucket::capacity_for(self.rate));
+        self.last_refill = now;
+    }
+
+    pub fn available(&self) -> usize {
+        if self.tokens <= 0 {
+            return 0;
+        }
+        return self.tokens as usize;
+    }
+
+    pub fn take(&mut self, bytes: usize) {
+        self.tokens -= bytes as i64;
+    }
+}
+
+// Weighted deficit round robin.  Each round a channel with queued messages earns quantum * weight, and sends
+// while it has earned enough.  Channels are visited in priority order so higher priorities get the budget first
+// when it runs out mid round, while the weights guarantee every channel it's share over time.
+// send(index, max_bytes) sends up to about max_bytes from the channel at index and returns bytes sent and
+// whether it still has messages queued.
+pub fn schedule<S>(deficits: &mut [i64], weights: &[u32], queued: &mut [bool], mut budget: usize, mut send: S) -> usize
+where
+    S: FnMut(usize, usize) -> (usize, bool),
+{
+    let mut total_sent = 0;
+
+    loop {
+        let mut progress = false;
+
+        for i in 0..deficits.len() {
+            if !queued[i] {
+                deficits[i] = 0;
+                continue;
+            }
+
+            if budget == 0 {
+                return total_sent;
+            }
+
+            // still paying off going over last time, it sends in a later round
+            deficits[i] += SCHEDULER_QUANTUM * std::cmp::max(weights[i], 1) as i64;
+            if deficits[i] <= 0 {
+                progress = true;
+                continue;
+            }
+
+            let max_bytes = std::cmp::min(deficits[i] as usize, budget);
+            let (sent, still_queued) = send(i, max_bytes);
+            queued[i] = still_queued;
+            if sent == 0 {
+                // blocked on it's send window, credit doesn't build up while waiting
+                deficits[i] = 0;
+                continue;
+            }
+            progress = true;
+
+            deficits[i] -= sent as i64;
+            budget = budget.saturating_sub(sent);
+            total_sent += sent;
+
+            // nothing carries over once the queue is empty
+            if !still_queued {
+                deficits[i] = 0;
+            }
+        }
+
+        // empty queues or full send windows
+        if !progress {
+            return total_sent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_weighted_share() {
+        let mut deficits: Vec<i64> = vec![0; 2];
+        let weights: Vec<u32> = vec![3, 1];
+        let mut queued: Vec<bool> = vec![true; 2];
+        let mut sent: Vec<usize> = vec![0; 2];
+
+        // 100 byte messages, always something queued
+        schedule(&mut deficits, &weights, &mut queued, 40000, |i, max_bytes| {
+            let mut bytes = 0;
+            while bytes + 100 <= max_bytes {
+                bytes += 100;
+            }
+            sent[i] += bytes;
+            return (bytes, true);
+        });
+
+        assert_eq!(40000, sent[0] + sent[1]);
+        assert!(sent[0] > sent[1] * 2);
+        assert!(sent[1] >= 8000);
+    }
+
+    #[test]
+    fn test_bucket() {
+        let mut bucket = TokenBucket::create(10000);
+        assert_eq!(1024, bucket.available());
+        bucket.take(2000);
+        assert_eq!(0, bucket.available());
+
+        let later = Instant::now() + Duration::from_millis(200);
+        bucket.refill(later);
+        assert_eq!(1024, bucket.available());
+    }
+}
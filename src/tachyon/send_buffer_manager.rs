@@ -2,22 +2,35 @@ use std::time::Instant;
 
 use super::{sequence::Sequence, sequence_buffer::SequenceBuffer, byte_buffer_pool::{ByteBuffer, ByteBufferPool, BYTE_BUFFER_SIZE_DEFAULT}};
 
-const SEND_BUFFER_SIZE: u16 = 1024;
-const EXPIRE: u128 = 5000;
+pub const SEND_BUFFER_SIZE: u16 = 1024;
 
 pub struct SendBuffer {
     pub sequence: u16,
     pub byte_buffer: ByteBuffer,
     pub created_at: Instant,
+    // milliseconds after created_at it's no longer resent, 0 for never
+    pub ttl: u32,
+    // set once it's resent by a nack or on a latest channel
+    pub resent: bool,
 }
 pub struct SendBufferManager {
     pub current_sequence: u16,
+    pub acked_sequence: u16,
     pub buffers: SequenceBuffer<SendBuffer>,
+    // sequences the receiver skipped or that expired, acks cover them but they were never delivered
+    pub lost: SequenceBuffer<u16>,
+    // ttl given to new buffers
+    pub ttl: u32,
     pub buffer_pool: ByteBufferPool
 }
 
 impl SendBufferManager {
     pub fn default() -> Self {
+        return SendBufferManager::create(BYTE_BUFFER_SIZE_DEFAULT);
+    }
+
+    // buffer_size is the max datagram size, larger buffers are allocated outside the pool
+    pub fn create(buffer_size: usize) -> Self {
         let mut buffers: SequenceBuffer<SendBuffer> = SequenceBuffer {
             values: Vec::new(),
             partition_by: SEND_BUFFER_SIZE,
@@ -28,8 +41,14 @@ impl SendBufferManager {
 
         let sender = SendBufferManager {
             current_sequence: 0,
+            acked_sequence: 0,
             buffers,
-            buffer_pool: ByteBufferPool::create(BYTE_BUFFER_SIZE_DEFAULT,SEND_BUFFER_SIZE as usize)
+            lost: SequenceBuffer {
+                values: vec![None; SEND_BUFFER_SIZE as usize],
+                partition_by: SEND_BUFFER_SIZE,
+            },
+            ttl: 0,
+            buffer_pool: ByteBufferPool::create(buffer_size,SEND_BUFFER_SIZE as usize)
         };
         return sender;
     }
@@ -45,18 +64,84 @@ impl SendBufferManager {
         }
     }
 
-    pub fn expire(&mut self) {
-        let mut expired: Vec<u16> = Vec::new();
+    // true if the buffer at sequence is still the one sent with that sequence
+    pub fn is_buffered(&self, sequence: u16) -> bool {
+        match self.buffers.get(sequence) {
+            Some(send_buffer) => {
+                return send_buffer.sequence == sequence;
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    pub fn is_acked(&self, sequence: u16) -> bool {
+        return Sequence::is_equal_to_or_less_than(sequence, self.acked_sequence) && !self.is_lost(sequence);
+    }
 
-        for value in &self.buffers.values {
-            if let Some(buffer) = value {
-                if buffer.created_at.elapsed().as_millis() > EXPIRE {
-                    expired.push(buffer.sequence);
+    pub fn is_lost(&self, sequence: u16) -> bool {
+        return self.lost.get(sequence) == Some(&sequence);
+    }
+
+    pub fn set_lost(&mut self, sequence: u16) {
+        self.lost.insert(sequence, sequence);
+    }
+
+    // Cumulative ack from the receiver.  Everything up to and including sequence was received (or skipped, see lost) by
+    // the other side and will never be nacked, so we can release those buffers now instead of waiting for them to be overwritten.
+    // Returns the number of buffers released.
+    pub fn ack(&mut self, sequence: u16) -> u32 {
+        if !Sequence::is_greater_then(sequence, self.acked_sequence) {
+            return 0;
+        }
+
+        // can't ack what we haven't sent
+        if Sequence::is_greater_then(sequence, self.current_sequence) {
+            return 0;
+        }
+
+        let mut released = 0;
+        let mut seq = sequence;
+        for _ in 0..SEND_BUFFER_SIZE {
+            if seq == self.acked_sequence {
+                break;
+            }
+            if self.is_buffered(seq) {
+                if let Some(send_buffer) = self.buffers.take(seq) {
+                    self.buffer_pool.return_buffer(send_buffer.byte_buffer);
+                    released += 1;
                 }
             }
+            seq = Sequence::previous_sequence(seq);
         }
-        for sequence in expired {
-            self.buffers.remove(sequence);
+
+        self.acked_sequence = sequence;
+        return released;
+    }
+
+    // Release unacked buffers older then their ttl, adding their sequences to expired.  Acked buffers are already
+    // released so only the unacked range is walked.
+    pub fn expire(&mut self, now: Instant, expired: &mut Vec<u16>) {
+        let count = std::cmp::min(Sequence::distance(self.acked_sequence, self.current_sequence), SEND_BUFFER_SIZE);
+        let mut seq = self.acked_sequence;
+        for _ in 0..count {
+            seq = Sequence::next_sequence(seq);
+
+            let is_expired = match self.buffers.get(seq) {
+                Some(buffer) => {
+                    buffer.sequence == seq && buffer.ttl > 0 && now.duration_since(buffer.created_at).as_millis() > buffer.ttl as u128
+                }
+                None => false
+            };
+
+            if is_expired {
+                if let Some(send_buffer) = self.buffers.take(seq) {
+                    self.buffer_pool.return_buffer(send_buffer.byte_buffer);
+                }
+                self.set_lost(seq);
+                expired.push(seq);
+            }
         }
     }
 
@@ -70,6 +155,8 @@ impl SendBufferManager {
             sequence: self.current_sequence,
             byte_buffer,
             created_at: Instant::now(),
+            ttl: self.ttl,
+            resent: false,
         };
 
         self.buffers.insert(self.current_sequence, buffer);
@@ -86,6 +173,7 @@ impl SendBufferManager {
 
     pub fn create_send_buffer(&mut self, length: usize) -> Option<&mut SendBuffer> {
         self.current_sequence = Sequence::next_sequence(self.current_sequence);
+        self.lost.remove(self.current_sequence);
 
         if let Some(mut send_buffer) = self.buffers.take(self.current_sequence) {
             if send_buffer.byte_buffer.pooled && length <= self.buffer_pool.buffer_size {
@@ -96,6 +184,7 @@ impl SendBufferManager {
             }
             send_buffer.sequence = self.current_sequence;
             send_buffer.created_at = Instant::now();
+            send_buffer.ttl = self.ttl;
             return self.buffers.insert(self.current_sequence, send_buffer);
         }
 
@@ -104,6 +193,8 @@ impl SendBufferManager {
             sequence: self.current_sequence,
             byte_buffer,
             created_at: Instant::now(),
+            ttl: self.ttl,
+            resent: false,
         };
         return self.buffers.insert(self.current_sequence, send_buffer);
         
@@ -154,17 +245,61 @@ mod tests {
         assert_eq!(BYTE_BUFFER_SIZE_DEFAULT + 10, buffer.byte_buffer.length);
     }
 
+    #[test]
+    fn test_ack() {
+        let mut manager = SendBufferManager::default();
+        for _ in 0..5 {
+            manager.create_send_buffer(32);
+        }
+
+        assert_eq!(3, manager.ack(3));
+        assert_eq!(3, manager.acked_sequence);
+        assert!(!manager.is_buffered(1));
+        assert!(!manager.is_buffered(3));
+        assert!(manager.is_buffered(4));
+        assert!(manager.is_acked(3));
+        assert!(!manager.is_acked(4));
+
+        // older and unsent acks are ignored
+        assert_eq!(0, manager.ack(2));
+        assert_eq!(0, manager.ack(6));
+        assert_eq!(3, manager.acked_sequence);
+
+        assert_eq!(2, manager.ack(5));
+        assert!(!manager.is_buffered(5));
+
+        // skipped by the receiver
+        manager.set_lost(4);
+        assert!(!manager.is_acked(4));
+        assert!(manager.is_acked(5));
+    }
+
     #[test]
     fn test_expire() {
         let mut buffers = SendBufferManager::default();
+        buffers.ttl = 100;
         let buffer = buffers.create_send_buffer(32);
         let buffer = buffer.unwrap();
         let sequence = buffer.sequence;
-        let now = Instant::now() - Duration::new(6, 0);
-        buffer.created_at = now;
 
+        // no ttl
+        buffers.ttl = 0;
+        buffers.create_send_buffer(32);
+
+        let mut expired: Vec<u16> = Vec::new();
+        buffers.expire(Instant::now(), &mut expired);
+        assert_eq!(0, expired.len());
+
+        let later = Instant::now() + Duration::from_millis(101);
         assert!(buffers.buffers.is_some(sequence));
-        buffers.expire();
+        buffers.expire(later, &mut expired);
         assert!(!buffers.buffers.is_some(sequence));
+        assert_eq!(vec![sequence], expired);
+        assert!(buffers.is_buffered(sequence + 1));
+
+        // an ack covering an expired message doesn't deliver it
+        buffers.ack(sequence + 1);
+        assert!(!buffers.is_acked(sequence));
+        assert!(buffers.is_acked(sequence + 1));
     }
 }
@@ -24,6 +24,15 @@ impl Sequence {
         }
     }
 
+    // how many sequences from s1 forward to s2
+    pub fn distance(s1: u16, s2: u16) -> u16 {
+        if s2 >= s1 {
+            return s2 - s1;
+        } else {
+            return std::u16::MAX - s1 + s2;
+        }
+    }
+
     pub fn previous_sequence(sequence: u16) -> u16 {
         if sequence == 0 {
             return std::u16::MAX - 1;
@@ -48,5 +57,9 @@ mod tests {
         assert_eq!(0, Sequence::previous_sequence(1));
 
         assert!(Sequence::is_greater_then(0, 65534));
+
+        assert_eq!(3, Sequence::distance(2, 5));
+        assert_eq!(2, Sequence::distance(65534, 1));
+        assert_eq!(0, Sequence::distance(7, 7));
     }
 }
@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+
+use super::sequence::Sequence;
+
+// most fragments a single stream message can have, keeps a message well inside half the sequence space
+pub const STREAM_MAX_FRAGMENTS: usize = 16384;
+
+pub struct StreamMessage {
+    pub data: Vec<u8>,
+    // bytes already sent as fragments
+    pub offset: usize,
+    pub fragmented: bool,
+    pub group: u16,
+    pub start_sequence: u16,
+    pub fragment_count: u16,
+    // the channel's fragment size can change while queued
+    pub fragment_size: usize,
+    // applied to the send buffers when it goes out
+    pub ttl: u32,
+}
+
+// Messages queued on a stream channel.  Sequences are reserved when a message is queued, since everything on the
+// channel goes through the queue the send buffers hand them out in the same order when the message is sent.
+pub struct StreamQueue {
+    pub messages: VecDeque<StreamMessage>,
+    // last sequence handed out to a queued message
+    pub reserved_sequence: u16,
+    pub queued_bytes: usize,
+}
+
+impl StreamQueue {
+    pub fn default() -> Self {
+        let queue = StreamQueue {
+            messages: VecDeque::new(),
+            reserved_sequence: 0,
+            queued_bytes: 0,
+        };
+        return queue;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.messages.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.messages.is_empty();
+    }
+
+    // current_sequence is the send buffers current sequence, where reservations start from when nothing is queued
+    pub fn queue(&mut self, data: &[u8], fragment_size: usize, fragment_count: u16, group: u16, current_sequence: u16, ttl: u32) -> &StreamMessage {
+        if self.messages.len() == 0 {
+            self.reserved_sequence = current_sequence;
+        }
+
+        let start_sequence = Sequence::next_sequence(self.reserved_sequence);
+        for _ in 0..fragment_count {
+            self.reserved_sequence = Sequence::next_sequence(self.reserved_sequence);
+        }
+
+        let message = StreamMessage {
+            data: data.to_vec(),
+            offset: 0,
+            fragmented: group > 0,
+            group,
+            start_sequence,
+            fragment_count,
+            fragment_size,
+            ttl,
+        };
+        self.queued_bytes += message.data.len();
+        self.messages.push_back(message);
+        return self.messages.back().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_sequences() {
+        let mut queue = StreamQueue::default();
+        let data: Vec<u8> = vec![0; 100];
+
+        // 65534, 0, 1
+        let message = queue.queue(&data, 40, 3, 1, 65533, 0);
+        assert_eq!(65534, message.start_sequence);
+        assert!(message.fragmented);
+
+        let message = queue.queue(&data, 1200, 1, 0, 65533, 0);
+        assert_eq!(2, message.start_sequence);
+        assert!(!message.fragmented);
+        assert_eq!(200, queue.queued_bytes);
+
+        // empty queue starts again from the current sequence
+        assert!(!queue.is_empty());
+        queue.messages.clear();
+        assert!(queue.is_empty());
+        let message = queue.queue(&data, 1200, 1, 0, 10, 0);
+        assert_eq!(11, message.start_sequence);
+    }
+}
@@ -1,8 +1,14 @@
 use std::{
     io,
     net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    time::Duration,
 };
 
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+
 use rand::{prelude::StdRng, Rng, SeedableRng};
 use socket2::{Domain, Socket, Type};
 
@@ -54,21 +60,36 @@ impl TachyonSocket {
     }
 
     pub fn bind_socket(&mut self, naddress: NetworkAddress) -> CreateConnectResult {
+        match self.try_bind_socket(naddress) {
+            Ok(()) => {
+                return CreateConnectResult::Success;
+            }
+            Err(_) => {
+                return CreateConnectResult::Error;
+            }
+        }
+    }
+
+    pub fn try_bind_socket(&mut self, naddress: NetworkAddress) -> io::Result<()> {
         if self.socket.is_some() {
-            return CreateConnectResult::Error;
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "socket already created"));
         }
 
         let address = naddress.to_socket_addr();
         self.address = naddress;
 
-        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
-        match socket.bind(&address.into()) {
-            Ok(()) => {
-                socket.set_recv_buffer_size(8192 * 256).unwrap();
-                socket.set_nonblocking(true).unwrap();
-                self.socket = Some(socket.into());
-                self.is_server = true;
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+        socket.bind(&address.into())?;
+        socket.set_recv_buffer_size(8192 * 256)?;
+        socket.set_nonblocking(true)?;
+        self.socket = Some(socket.into());
+        self.is_server = true;
+        return Ok(());
+    }
 
+    pub fn connect_socket(&mut self, naddress: NetworkAddress) -> CreateConnectResult {
+        match self.try_connect_socket(naddress) {
+            Ok(()) => {
                 return CreateConnectResult::Success;
             }
             Err(_) => {
@@ -77,36 +98,100 @@ impl TachyonSocket {
         }
     }
 
-    pub fn connect_socket(&mut self, naddress: NetworkAddress) -> CreateConnectResult {
+    pub fn try_connect_socket(&mut self, naddress: NetworkAddress) -> io::Result<()> {
         if self.socket.is_some() {
-            return CreateConnectResult::Error;
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "socket already created"));
         }
 
         self.address = NetworkAddress::default();
         let sock_addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0);
-        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).unwrap();
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+        socket.bind(&sock_addr.into())?;
+        socket.set_recv_buffer_size(8192 * 256)?;
+        socket.set_nonblocking(true)?;
 
-        match socket.bind(&sock_addr.into()) {
-            Ok(()) => {
-                socket.set_recv_buffer_size(8192 * 256).unwrap();
-                socket.set_nonblocking(true).unwrap();
-                let address = naddress.to_socket_addr();
-                let udp_socket: UdpSocket = socket.into();
-
-                match udp_socket.connect(&address) {
-                    Ok(()) => {
-                        self.socket = Some(udp_socket);
-                        return CreateConnectResult::Success;
-                    }
-                    Err(_) => {
-                        return CreateConnectResult::Error;
-                    }
-                }
+        let address = naddress.to_socket_addr();
+        let udp_socket: UdpSocket = socket.into();
+        udp_socket.connect(&address)?;
+        self.socket = Some(udp_socket);
+        return Ok(());
+    }
+
+    // needed to send to a broadcast address
+    pub fn set_broadcast(&self, broadcast: bool) -> io::Result<()> {
+        match &self.socket {
+            Some(socket) => {
+                return socket.set_broadcast(broadcast);
             }
-            Err(_) => {
-                return CreateConnectResult::Error;
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "bind first"));
+            }
+        }
+    }
+
+    // receive datagrams sent to the group on any interface, the port is ignored
+    pub fn join_multicast(&self, group: NetworkAddress) -> io::Result<()> {
+        let ip = Ipv4Addr::new(group.a as u8, group.b as u8, group.c as u8, group.d as u8);
+        match &self.socket {
+            Some(socket) => {
+                return socket.join_multicast_v4(&ip, &Ipv4Addr::UNSPECIFIED);
+            }
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "bind first"));
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        return self.socket.as_ref().map(|socket| socket.as_raw_fd());
+    }
+
+    #[cfg(windows)]
+    pub fn raw_socket(&self) -> Option<RawSocket> {
+        return self.socket.as_ref().map(|socket| socket.as_raw_socket());
+    }
+
+    // Blocks until a datagram can be read or the timeout passes, false on timeout.  Errors count as readable so
+    // the receive that follows sees them.
+    #[cfg(unix)]
+    pub fn wait_readable(&self, timeout: Duration) -> bool {
+        let fd = match self.raw_fd() {
+            Some(fd) => fd,
+            None => {
+                return false;
+            }
+        };
+
+        let mut pollfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+        // rounded up so we don't wake just short of the timeout
+        let millis = std::cmp::min((timeout.as_micros() + 999) / 1000, i32::MAX as u128) as libc::c_int;
+        let res = unsafe { libc::poll(&mut pollfd, 1, millis) };
+        return res != 0;
+    }
+
+    // No poll here, block on a peek with a read timeout and go back to non blocking.
+    #[cfg(not(unix))]
+    pub fn wait_readable(&self, timeout: Duration) -> bool {
+        let socket = match &self.socket {
+            Some(socket) => socket,
+            None => {
+                return false;
             }
+        };
+
+        if timeout.is_zero() || socket.set_nonblocking(false).is_err() {
+            return false;
         }
+        let _ = socket.set_read_timeout(Some(timeout));
+        let mut buffer: [u8; 1] = [0; 1];
+        let readable = match socket.peek_from(&mut buffer) {
+            Ok(_) => true,
+            Err(error) => error.kind() != io::ErrorKind::WouldBlock && error.kind() != io::ErrorKind::TimedOut,
+        };
+        let _ = socket.set_read_timeout(None);
+        let _ = socket.set_nonblocking(true);
+        return readable;
     }
 
     fn should_drop(&mut self, data: &mut [u8], drop_chance: u64, drop_reliable_only: bool) -> bool {
@@ -173,29 +258,166 @@ impl TachyonSocket {
     }
 
     pub fn send_to(&self, address: NetworkAddress, data: &[u8], length: usize) -> usize {
+        match self.try_send_to(address, data, length) {
+            Ok(size) => {
+                return size;
+            }
+            Err(_) => {
+                return 0;
+            }
+        }
+    }
+
+    pub fn try_send_to(&self, address: NetworkAddress, data: &[u8], length: usize) -> io::Result<usize> {
         match &self.socket {
             Some(socket) => {
                 let slice = &data[0..length];
-                let socket_result: io::Result<usize>;
-
                 if address.port == 0 {
-                    socket_result = socket.send(slice);
+                    return socket.send(slice);
                 } else {
-                    socket_result = socket.send_to(slice, address.to_socket_addr());
+                    return socket.send_to(slice, address.to_socket_addr());
                 }
+            }
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "bind or connect first"));
+            }
+        }
+    }
 
-                match socket_result {
-                    Ok(size) => {
-                        return size;
-                    }
-                    Err(_) => {
-                        return 0;
-                    }
-                }
+    // Sent with don't fragment set, so a probe larger then the path allows is dropped or refused with EMSGSIZE
+    // instead of being fragmented by IP.  Platforms without support send it as is.
+    pub fn send_probe(&self, address: NetworkAddress, data: &[u8], length: usize) -> io::Result<usize> {
+        let dont_fragment = self.set_dont_fragment(true).is_ok();
+        let result = self.try_send_to(address, data, length);
+        if dont_fragment {
+            let _ = self.set_dont_fragment(false);
+        }
+        return result;
+    }
+
+    // false restores the system default
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_dont_fragment(&self, dont_fragment: bool) -> io::Result<()> {
+        let value: libc::c_int = if dont_fragment { libc::IP_PMTUDISC_DO } else { libc::IP_PMTUDISC_WANT };
+        return self.set_ip_option(libc::IP_MTU_DISCOVER, value);
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    pub fn set_dont_fragment(&self, dont_fragment: bool) -> io::Result<()> {
+        return self.set_ip_option(libc::IP_DONTFRAG, dont_fragment as libc::c_int);
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+    pub fn set_dont_fragment(&self, _dont_fragment: bool) -> io::Result<()> {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "don't fragment not supported"));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    fn set_ip_option(&self, option: libc::c_int, value: libc::c_int) -> io::Result<()> {
+        let fd = match self.raw_fd() {
+            Some(fd) => fd,
+            None => {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "bind or connect first"));
+            }
+        };
+        let res = unsafe {
+            libc::setsockopt(fd, libc::IPPROTO_IP, option, &value as *const libc::c_int as *const libc::c_void, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok(());
+    }
+
+    // the send was larger then the socket or path allows
+    pub fn is_message_too_long(error: &io::Error) -> bool {
+        #[cfg(unix)]
+        let code = libc::EMSGSIZE;
+        // WSAEMSGSIZE
+        #[cfg(not(unix))]
+        let code = 10040;
+        return error.raw_os_error() == Some(code);
+    }
+}
+
+// Lets a Tachyon join an existing mio event loop, register after bind or connect.
+#[cfg(all(feature = "mio", unix))]
+impl mio::event::Source for TachyonSocket {
+    fn register(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()> {
+        return mio::unix::SourceFd(&self.source_fd()?).register(registry, token, interests);
+    }
+
+    fn reregister(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()> {
+        return mio::unix::SourceFd(&self.source_fd()?).reregister(registry, token, interests);
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        return mio::unix::SourceFd(&self.source_fd()?).deregister(registry);
+    }
+}
+
+#[cfg(all(feature = "mio", unix))]
+impl TachyonSocket {
+    fn source_fd(&self) -> io::Result<RawFd> {
+        match self.raw_fd() {
+            Some(fd) => {
+                return Ok(fd);
             }
             None => {
-                return 0;
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "bind or connect first"));
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::tachyon::network_address::NetworkAddress;
+
+    use super::*;
+
+    #[test]
+    #[serial]
+    fn test_send_probe() {
+        let address = NetworkAddress::test_address();
+        let mut server = TachyonSocket::create();
+        server.bind_socket(address);
+        let mut client = TachyonSocket::create();
+        client.connect_socket(address);
+
+        let data: Vec<u8> = vec![0; 70000];
+        assert_eq!(1000, client.send_probe(NetworkAddress::default(), &data, 1000).unwrap());
+        let error = client.send_probe(NetworkAddress::default(), &data, 70000).unwrap_err();
+        assert!(TachyonSocket::is_message_too_long(&error));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(all(feature = "mio", unix))]
+    fn test_mio_source() {
+        use crate::tachyon::{pool::SendTarget, Tachyon, TachyonConfig};
+
+        let address = NetworkAddress::test_address();
+        let mut server = Tachyon::create(TachyonConfig::default());
+        let mut poll = mio::Poll::new().unwrap();
+        assert!(poll.registry().register(&mut server.socket, mio::Token(1), mio::Interest::READABLE).is_err());
+
+        server.bind(address);
+        poll.registry().register(&mut server.socket, mio::Token(1), mio::Interest::READABLE).unwrap();
+
+        let mut client = Tachyon::create(TachyonConfig::default());
+        client.connect(address);
+        let mut data: Vec<u8> = vec![0; 64];
+        client.send_to_target(1, SendTarget { address: NetworkAddress::default(), identity_id: 0 }, &mut data, 64);
+
+        let mut events = mio::Events::with_capacity(4);
+        poll.poll(&mut events, Some(Duration::from_millis(1000))).unwrap();
+        assert_eq!(Some(mio::Token(1)), events.iter().next().map(|event| event.token()));
+
+        let mut receive_buffer: Vec<u8> = vec![0; 4096];
+        assert_eq!(64, server.receive_loop(&mut receive_buffer).length);
+        poll.registry().deregister(&mut server.socket).unwrap();
+    }
+}
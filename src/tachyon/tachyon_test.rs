@@ -126,6 +126,11 @@ impl TachyonTest {
         return self.client.send_to_target(0,target, &mut self.send_buffer, length);
     }
 
+    pub fn client_send_unreliable_channel(&mut self, channel_id: u8, length: usize) -> TachyonSendResult {
+        let target = SendTarget {address: self.client_address, identity_id: 0};
+        return self.client.send_unreliable_to_target(channel_id, target, &mut self.send_buffer, length);
+    }
+
     pub fn server_receive(&mut self) -> TachyonReceiveResult {
         return self.server.receive_loop(&mut self.receive_buffer);
     }
@@ -0,0 +1,168 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::{network_address::NetworkAddress, pool::SendTarget, TachyonSendResult, SEND_ERROR_UNKNOWN_TARGET};
+
+#[derive(Clone, Copy, Default, Debug)]
+#[repr(C)]
+pub struct PublishResult {
+    pub sent: u32,
+    // subscribers with no connection, or whose send failed
+    pub failed: u32,
+    // error of the last failed send
+    pub error: u32,
+    pub sent_len: u32,
+}
+
+impl PublishResult {
+    pub(crate) fn count(&mut self, error: u32, sent_len: u32) {
+        if error == 0 {
+            self.sent += 1;
+            self.sent_len += sent_len;
+        } else {
+            self.failed += 1;
+            self.error = error;
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: PublishResult) {
+        self.sent += other.sent;
+        self.failed += other.failed;
+        self.sent_len += other.sent_len;
+        if other.error != 0 {
+            self.error = other.error;
+        }
+    }
+
+    // for send_to_target with a broadcast address, an error only if every send failed or there was no one to send to
+    pub fn to_send_result(&self) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+        result.sent_len = self.sent_len;
+        if self.sent + self.failed == 0 {
+            result.error = SEND_ERROR_UNKNOWN_TARGET;
+        } else if self.sent == 0 {
+            result.error = self.error;
+        }
+        return result;
+    }
+}
+
+// Subscribers by topic.  A subscriber is a SendTarget, by identity it follows the identity across relinks, by
+// address it's removed when the connection is.
+pub struct Topics {
+    topics: FxHashMap<u32, FxHashSet<SendTarget>>,
+}
+
+impl Topics {
+    pub fn create() -> Self {
+        let topics = Topics {
+            topics: FxHashMap::default(),
+        };
+        return topics;
+    }
+
+    // false if already subscribed
+    pub fn subscribe(&mut self, topic: u32, target: SendTarget) -> bool {
+        return self.topics.entry(topic).or_default().insert(Topics::key(target));
+    }
+
+    pub fn unsubscribe(&mut self, topic: u32, target: SendTarget) -> bool {
+        let mut removed = false;
+        if let Some(subscribers) = self.topics.get_mut(&topic) {
+            removed = subscribers.remove(&Topics::key(target));
+            if subscribers.is_empty() {
+                self.topics.remove(&topic);
+            }
+        }
+        return removed;
+    }
+
+    pub fn unsubscribe_all(&mut self, target: SendTarget) {
+        let key = Topics::key(target);
+        self.retain(|subscriber| *subscriber != key);
+    }
+
+    // address subscribers for which keep returns false are removed
+    pub fn retain_addresses<F>(&mut self, mut keep: F) where F: FnMut(NetworkAddress) -> bool {
+        self.retain(|subscriber| subscriber.identity_id > 0 || keep(subscriber.address));
+    }
+
+    fn retain<F>(&mut self, mut keep: F) where F: FnMut(&SendTarget) -> bool {
+        for subscribers in self.topics.values_mut() {
+            subscribers.retain(|subscriber| keep(subscriber));
+        }
+        self.topics.retain(|_, subscribers| !subscribers.is_empty());
+    }
+
+    pub fn get_subscribers(&self, topic: u32) -> Vec<SendTarget> {
+        match self.topics.get(&topic) {
+            Some(subscribers) => {
+                return subscribers.iter().copied().collect();
+            }
+            None => {
+                return Vec::new();
+            }
+        }
+    }
+
+    pub fn get_subscriber_count(&self, topic: u32) -> u32 {
+        match self.topics.get(&topic) {
+            Some(subscribers) => {
+                return subscribers.len() as u32;
+            }
+            None => {
+                return 0;
+            }
+        }
+    }
+
+    // identity targets ignore the address
+    fn key(target: SendTarget) -> SendTarget {
+        if target.identity_id > 0 {
+            return SendTarget { identity_id: target.identity_id, address: NetworkAddress::default() };
+        }
+        return target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribe() {
+        let mut topics = Topics::create();
+        let address = NetworkAddress::localhost(5000);
+        let by_address = SendTarget { identity_id: 0, address };
+        let by_identity = SendTarget { identity_id: 7, address };
+
+        assert!(topics.subscribe(1, by_address));
+        assert!(!topics.subscribe(1, by_address));
+        assert!(topics.subscribe(1, by_identity));
+        assert!(!topics.subscribe(1, SendTarget { identity_id: 7, address: NetworkAddress::default() }));
+        assert!(topics.subscribe(2, by_identity));
+        assert_eq!(2, topics.get_subscriber_count(1));
+
+        topics.retain_addresses(|_| false);
+        assert_eq!(1, topics.get_subscriber_count(1));
+        topics.unsubscribe_all(by_identity);
+        assert_eq!(0, topics.get_subscriber_count(1));
+        assert_eq!(0, topics.get_subscriber_count(2));
+        assert!(!topics.unsubscribe(1, by_address));
+    }
+
+    #[test]
+    fn test_to_send_result() {
+        let mut result = PublishResult::default();
+        assert_eq!(SEND_ERROR_UNKNOWN_TARGET, result.to_send_result().error);
+
+        result.failed = 1;
+        result.error = 5;
+        assert_eq!(5, result.to_send_result().error);
+
+        result.sent = 1;
+        result.sent_len = 40;
+        let send_result = result.to_send_result();
+        assert_eq!(0, send_result.error);
+        assert_eq!(40, send_result.sent_len);
+    }
+}
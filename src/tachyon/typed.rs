@@ -0,0 +1,317 @@
+use std::any::{type_name, TypeId};
+
+use rustc_hash::FxHashMap;
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    error::TachyonError, event::Event, int_buffer::IntBuffer, network_address::NetworkAddress, pool::{Pool, SendTarget},
+    Tachyon, TachyonSendResult,
+};
+
+// type id in front of every typed message
+pub const TYPE_ID_SIZE: usize = 2;
+
+// Where a typed message came from, passed to handlers
+#[derive(Clone, Copy, Debug)]
+pub struct MessageInfo {
+    pub channel: u8,
+    pub address: NetworkAddress,
+    // 0 when identities are not used
+    pub identity: u32,
+    pub type_id: u16,
+}
+
+type Handler = Box<dyn FnMut(&[u8], MessageInfo) -> Result<(), TachyonError> + Send>;
+
+// Sends and receives serde types over the regular send and receive functions.  A message is the u16 type id
+// followed by the bincode encoded value.  Both sides register the same types with the same ids, receivers
+// dispatch to a handler per type.  Typed and raw messages can share a channel only if the raw ones never
+// start with a registered type id, so giving typed messages their own channel is simplest.
+pub struct MessageRegistry {
+    type_ids: FxHashMap<TypeId, u16>,
+    types: FxHashMap<u16, TypeId>,
+    handlers: FxHashMap<u16, Handler>,
+    send_buffer: Vec<u8>,
+    receive_buffer: Vec<u8>,
+}
+
+impl MessageRegistry {
+    pub fn create() -> Self {
+        let registry = MessageRegistry {
+            type_ids: FxHashMap::default(),
+            types: FxHashMap::default(),
+            handlers: FxHashMap::default(),
+            send_buffer: vec![0; 1024],
+            receive_buffer: vec![0; 1024 * 64],
+        };
+        return registry;
+    }
+
+    // false if the type or the id is already registered
+    pub fn register<T>(&mut self, type_id: u16) -> bool where T: Serialize + DeserializeOwned + 'static {
+        if self.type_ids.contains_key(&TypeId::of::<T>()) || self.types.contains_key(&type_id) {
+            return false;
+        }
+        self.type_ids.insert(TypeId::of::<T>(), type_id);
+        self.types.insert(type_id, TypeId::of::<T>());
+        return true;
+    }
+
+    pub fn get_type_id<T>(&self) -> Option<u16> where T: 'static {
+        return self.type_ids.get(&TypeId::of::<T>()).copied();
+    }
+
+    // replaces any handler for T, T has to be registered
+    pub fn set_handler<T, F>(&mut self, mut handler: F) -> Result<(), TachyonError>
+    where
+        T: Serialize + DeserializeOwned + 'static,
+        F: FnMut(T, MessageInfo) + Send + 'static,
+    {
+        let type_id = self.type_id::<T>()?;
+        let decode = move |data: &[u8], info: MessageInfo| -> Result<(), TachyonError> {
+            let message: T = decode_body(data)?;
+            handler(message, info);
+            return Ok(());
+        };
+        self.handlers.insert(type_id, Box::new(decode));
+        return Ok(());
+    }
+
+    pub fn remove_handler<T>(&mut self) -> bool where T: 'static {
+        match self.get_type_id::<T>() {
+            Some(type_id) => {
+                return self.handlers.remove(&type_id).is_some();
+            }
+            None => {
+                return false;
+            }
+        }
+    }
+
+    fn type_id<T>(&self) -> Result<u16, TachyonError> where T: 'static {
+        match self.get_type_id::<T>() {
+            Some(type_id) => {
+                return Ok(type_id);
+            }
+            None => {
+                return Err(TachyonError::UnregisteredType(type_name::<T>()));
+            }
+        }
+    }
+
+    // encodes into the registry's buffer, returns the message length
+    fn encode<T>(&mut self, message: &T) -> Result<usize, TachyonError> where T: Serialize + 'static {
+        let type_id = self.type_id::<T>()?;
+        let body_len = match bincode::serialized_size(message) {
+            Ok(size) => size as usize,
+            Err(error) => {
+                return Err(TachyonError::Encode(error.to_string()));
+            }
+        };
+
+        let length = TYPE_ID_SIZE + body_len;
+        if self.send_buffer.len() < length {
+            self.send_buffer.resize(length, 0);
+        }
+        let mut writer = IntBuffer { index: 0 };
+        writer.write_u16(type_id, &mut self.send_buffer);
+        if let Err(error) = bincode::serialize_into(&mut self.send_buffer[TYPE_ID_SIZE..length], message) {
+            return Err(TachyonError::Encode(error.to_string()));
+        }
+        return Ok(length);
+    }
+
+    pub fn send<T>(&mut self, tachyon: &mut Tachyon, channel: u8, target: SendTarget, message: &T) -> Result<TachyonSendResult, TachyonError>
+    where
+        T: Serialize + 'static,
+    {
+        let length = self.encode(message)?;
+        return tachyon.try_send_to_target(channel, target, &mut self.send_buffer, length);
+    }
+
+    pub fn send_pool<T>(&mut self, pool: &mut Pool, channel: u8, target: SendTarget, message: &T) -> Result<TachyonSendResult, TachyonError>
+    where
+        T: Serialize + 'static,
+    {
+        let length = self.encode(message)?;
+        return pool.try_send_to_target(channel, target, &mut self.send_buffer, length as i32);
+    }
+
+    // decodes a received message as T, fails if it was sent as another type
+    pub fn decode<T>(&self, data: &[u8]) -> Result<T, TachyonError> where T: DeserializeOwned + 'static {
+        let type_id = self.type_id::<T>()?;
+        match get_message_type(data) {
+            Some(message_type) if message_type == type_id => {
+                return decode_body(&data[TYPE_ID_SIZE..]);
+            }
+            Some(message_type) => {
+                return Err(TachyonError::UnknownMessageType(message_type));
+            }
+            None => {
+                return Err(TachyonError::Decode(String::from("message shorter then the type id")));
+            }
+        }
+    }
+
+    // runs the handler for the message's type.  Ok(false) if it's type has no handler, errors if the type id
+    // isn't registered or the message doesn't decode.
+    pub fn dispatch(&mut self, channel: u8, address: NetworkAddress, identity: u32, data: &[u8]) -> Result<bool, TachyonError> {
+        let type_id = match get_message_type(data) {
+            Some(type_id) => type_id,
+            None => {
+                return Err(TachyonError::Decode(String::from("message shorter then the type id")));
+            }
+        };
+        if !self.types.contains_key(&type_id) {
+            return Err(TachyonError::UnknownMessageType(type_id));
+        }
+
+        match self.handlers.get_mut(&type_id) {
+            Some(handler) => {
+                let info = MessageInfo { channel, address, identity, type_id };
+                handler(&data[TYPE_ID_SIZE..], info)?;
+                return Ok(true);
+            }
+            None => {
+                return Ok(false);
+            }
+        }
+    }
+
+    // for poll_events, Ok(false) for events that are not messages
+    pub fn dispatch_event(&mut self, event: &Event) -> Result<bool, TachyonError> {
+        match event {
+            Event::Message { channel, address, identity, data } => {
+                return self.dispatch(*channel, *address, *identity, data);
+            }
+            _ => {
+                return Ok(false);
+            }
+        }
+    }
+
+    // Receives and dispatches everything waiting on the Tachyon, returns the number dispatched.  Stops at the first
+    // error, messages after it stay queued for the next call.
+    pub fn receive(&mut self, tachyon: &mut Tachyon) -> Result<u32, TachyonError> {
+        let mut count = 0;
+        loop {
+            let result = match tachyon.try_receive(&mut self.receive_buffer) {
+                Ok(Some(result)) => result,
+                Ok(None) => {
+                    return Ok(count);
+                }
+                Err(TachyonError::BufferTooSmall { needed }) => {
+                    self.receive_buffer.resize(needed, 0);
+                    continue;
+                }
+                Err(error) => {
+                    return Err(error);
+                }
+            };
+
+            let identity = tachyon.get_connection_identity(result.address).id;
+            let length = result.length as usize;
+            let data = std::mem::take(&mut self.receive_buffer);
+            let dispatched = self.dispatch(result.channel as u8, result.address, identity, &data[0..length]);
+            self.receive_buffer = data;
+            if dispatched? {
+                count += 1;
+            }
+        }
+    }
+}
+
+// the type id of a typed message, None if it's too short to have one
+pub fn get_message_type(data: &[u8]) -> Option<u16> {
+    if data.len() < TYPE_ID_SIZE {
+        return None;
+    }
+    let mut reader = IntBuffer { index: 0 };
+    return Some(reader.read_u16(data));
+}
+
+fn decode_body<T>(body: &[u8]) -> Result<T, TachyonError> where T: DeserializeOwned {
+    match bincode::deserialize(body) {
+        Ok(message) => {
+            return Ok(message);
+        }
+        Err(error) => {
+            return Err(TachyonError::Decode(error.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde::Deserialize;
+    use serial_test::serial;
+
+    use crate::tachyon::tachyon_test::TachyonTest;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Move {
+        entity: u32,
+        position: (f32, f32),
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Chat {
+        text: String,
+    }
+
+    fn registry() -> MessageRegistry {
+        let mut registry = MessageRegistry::create();
+        assert!(registry.register::<Move>(1));
+        assert!(registry.register::<Chat>(2));
+        assert!(!registry.register::<Chat>(3));
+        assert!(!registry.register::<String>(1));
+        return registry;
+    }
+
+    #[test]
+    #[serial]
+    fn test_typed_messages() {
+        let mut test = TachyonTest::default();
+        test.connect();
+        let target = SendTarget { address: test.client_address, identity_id: 0 };
+
+        let mut client = registry();
+        let mut server = registry();
+        let moves: Arc<Mutex<Vec<Move>>> = Arc::new(Mutex::new(Vec::new()));
+        let handler_moves = moves.clone();
+        server.set_handler(move |message: Move, info: MessageInfo| {
+            assert_eq!(1, info.channel);
+            assert_eq!(1, info.type_id);
+            handler_moves.lock().unwrap().push(message);
+        }).unwrap();
+
+        client.send(&mut test.client, 1, target, &Move { entity: 7, position: (1.5, -2.0) }).unwrap();
+        client.send(&mut test.client, 1, target, &Chat { text: String::from("no handler") }).unwrap();
+        client.send(&mut test.client, 1, target, &Move { entity: 8, position: (0.0, 3.0) }).unwrap();
+        assert_eq!(2, server.receive(&mut test.server).unwrap());
+        assert_eq!(vec![Move { entity: 7, position: (1.5, -2.0) }, Move { entity: 8, position: (0.0, 3.0) }], *moves.lock().unwrap());
+
+        match client.send(&mut test.client, 1, target, &5u64) {
+            Err(TachyonError::UnregisteredType(_)) => {}
+            _ => panic!("expected UnregisteredType"),
+        }
+
+        // decode directly, and as the wrong type
+        let length = client.encode(&Chat { text: String::from("hello") }).unwrap();
+        let data = client.send_buffer[0..length].to_vec();
+        assert_eq!(Some(2), get_message_type(&data));
+        assert_eq!(Chat { text: String::from("hello") }, server.decode::<Chat>(&data).unwrap());
+        match server.decode::<Move>(&data) {
+            Err(TachyonError::UnknownMessageType(2)) => {}
+            _ => panic!("expected UnknownMessageType"),
+        }
+        match server.dispatch(1, test.client_address, 0, &[9, 0, 1]) {
+            Err(TachyonError::UnknownMessageType(9)) => {}
+            _ => panic!("expected UnknownMessageType"),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use std::{io, net::UdpSocket};
 
 use super::{
-    header::{Header, MESSAGE_TYPE_UNRELIABLE},
+    header::{Header, MESSAGE_TYPE_UNRELIABLE, MESSAGE_TYPE_UNRELIABLE_CHANNEL, TACHYON_HEADER_SIZE},
     network_address::NetworkAddress,
     TachyonSendResult, SEND_ERROR_CHANNEL, SEND_ERROR_LENGTH,
 };
@@ -26,7 +26,7 @@ impl UnreliableSender {
     pub fn send(&mut self, address: NetworkAddress, data: &mut [u8], body_len: usize) -> TachyonSendResult {
         let mut result = TachyonSendResult::default();
         
-        if body_len < 1 {
+        if body_len < 1 || body_len + 1 > self.send_buffer.len() {
             result.error = SEND_ERROR_LENGTH;
             return result;
         }
@@ -51,6 +51,36 @@ impl UnreliableSender {
         return result;
     }
 
+    // sequence is the channel's unreliable sequence
+    pub fn send_on_channel(&mut self, address: NetworkAddress, channel_id: u8, sequence: u16, data: &mut [u8], body_len: usize) -> TachyonSendResult {
+        let mut result = TachyonSendResult::default();
+
+        if body_len < 1 || body_len + TACHYON_HEADER_SIZE > self.send_buffer.len() {
+            result.error = SEND_ERROR_LENGTH;
+            return result;
+        }
+
+        if !self.socket.is_some() {
+            result.error = SEND_ERROR_CHANNEL;
+            return result;
+        }
+
+        self.send_buffer[TACHYON_HEADER_SIZE..body_len + TACHYON_HEADER_SIZE].copy_from_slice(&data[0..body_len]);
+        let length = body_len + TACHYON_HEADER_SIZE;
+
+        let mut header = Header::default();
+        header.message_type = MESSAGE_TYPE_UNRELIABLE_CHANNEL;
+        header.channel = channel_id;
+        header.sequence = sequence;
+        header.write(&mut self.send_buffer);
+
+        let sent_len = self.send_to(address, length);
+        result.sent_len = sent_len as u32;
+        result.header = header;
+
+        return result;
+    }
+
     fn send_to(&self, address: NetworkAddress, length: usize) -> usize {
         match &self.socket {
             Some(socket) => {
@@ -0,0 +1,83 @@
+use super::{sequence::Sequence, sequence_buffer::SequenceBuffer};
+
+// how far behind the newest sequence an unreliable message can arrive and still be delivered
+pub const UNRELIABLE_WINDOW_SIZE: u16 = 256;
+
+// Sequences for unreliable messages sent on a channel.  They are separate from the channel's reliable sequences,
+// nothing is buffered or resent.  The receiving side drops duplicates and anything older then the window.
+pub struct UnreliableSequence {
+    pub send_sequence: u16,
+    pub newest_received: u16,
+    // slot holds the sequence last received into it
+    received: SequenceBuffer<u16>,
+    pub out_of_order: u64,
+    pub dropped: u64,
+}
+
+impl UnreliableSequence {
+    pub fn default() -> Self {
+        let sequence = UnreliableSequence {
+            send_sequence: 0,
+            newest_received: 0,
+            received: SequenceBuffer {
+                values: vec![None; UNRELIABLE_WINDOW_SIZE as usize],
+                partition_by: UNRELIABLE_WINDOW_SIZE,
+            },
+            out_of_order: 0,
+            dropped: 0,
+        };
+        return sequence;
+    }
+
+    pub fn next_send_sequence(&mut self) -> u16 {
+        self.send_sequence = Sequence::next_sequence(self.send_sequence);
+        return self.send_sequence;
+    }
+
+    // returns false if the message should be dropped
+    pub fn receive(&mut self, sequence: u16) -> bool {
+        if self.received.get(sequence) == Some(&sequence) {
+            self.dropped += 1;
+            return false;
+        }
+
+        if Sequence::is_greater_then(sequence, self.newest_received) {
+            self.newest_received = sequence;
+        } else {
+            if Sequence::distance(sequence, self.newest_received) >= UNRELIABLE_WINDOW_SIZE {
+                self.dropped += 1;
+                return false;
+            }
+            self.out_of_order += 1;
+        }
+
+        self.received.insert(sequence, sequence);
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_receive() {
+        let mut sequence = UnreliableSequence::default();
+        assert!(sequence.receive(1));
+        assert!(sequence.receive(3));
+        assert!(!sequence.receive(3));
+
+        // late but inside the window
+        assert!(sequence.receive(2));
+        assert_eq!(1, sequence.out_of_order);
+
+        assert!(sequence.receive(400));
+        assert!(!sequence.receive(100));
+        assert_eq!(2, sequence.dropped);
+
+        // wraps
+        sequence.newest_received = 65534;
+        assert!(sequence.receive(0));
+        assert_eq!(0, sequence.newest_received);
+    }
+}